@@ -0,0 +1,100 @@
+use crate::code;
+use crate::instruction::Instruction;
+use crate::symbol::SymbolTable;
+
+/// First variable address handed out by the assembler.
+pub const VARIABLE_BASE: usize = 16;
+
+/// Records the ROM address of every label.
+pub fn init_symbol_table(instructs: &[Instruction], symbol_table: &mut SymbolTable) {
+    let mut rom_addr = 0;
+
+    for instruct in instructs {
+        match instruct {
+            Instruction::L(symbol) => symbol_table.add_entry(symbol, rom_addr),
+            Instruction::Comment(_) => {}
+            _ => rom_addr += 1,
+        }
+    }
+}
+
+/// Encodes one instruction as a binary string, allocating variables on first
+/// use. Labels and comments produce no output.
+pub fn encode(
+    instruct: &Instruction,
+    symbol_table: &mut SymbolTable,
+    variable_count: &mut usize,
+) -> Option<String> {
+    match instruct {
+        Instruction::C { dest, comp, jump } => {
+            let a_bit = if comp.contains('M') { 1 } else { 0 };
+
+            Some(format!(
+                "111{}{}{}{}",
+                a_bit,
+                code::comp(comp),
+                code::dest(dest.as_deref().unwrap_or("")),
+                code::jump(jump.as_deref().unwrap_or(""))
+            ))
+        }
+        Instruction::AConst(num) => Some(format!("{:016b}", num)),
+        Instruction::AVar(var) => {
+            if !symbol_table.contains(var) {
+                symbol_table.add_entry(var, VARIABLE_BASE + *variable_count);
+                *variable_count += 1;
+            }
+
+            Some(format!("{:016b}", symbol_table.get_address(var)))
+        }
+        Instruction::L(_) | Instruction::Comment(_) => None,
+    }
+}
+
+/// Resolves symbols and encodes a whole program, one binary string per word.
+pub fn assemble(instructs: &[Instruction]) -> Vec<String> {
+    let mut symbol_table = SymbolTable::new();
+    let mut variable_count = 0;
+
+    init_symbol_table(instructs, &mut symbol_table);
+
+    instructs
+        .iter()
+        .filter_map(|instruct| encode(instruct, &mut symbol_table, &mut variable_count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let instructs = [
+            Instruction::Comment("set x".to_string()),
+            Instruction::L("START".to_string()),
+            Instruction::AVar("x".to_string()),
+            Instruction::C {
+                dest: Some("M".to_string()),
+                comp: "M+D".to_string(),
+                jump: None,
+            },
+            Instruction::AVar("START".to_string()),
+            Instruction::C {
+                dest: None,
+                comp: "0".to_string(),
+                jump: Some("JMP".to_string()),
+            },
+            Instruction::AConst(21),
+        ];
+
+        let expected = [
+            "0000000000010000",
+            "1111000010001000",
+            "0000000000000000",
+            "1110101010000111",
+            "0000000000010101",
+        ];
+
+        assert_eq!(expected.to_vec(), assemble(&instructs));
+    }
+}
@@ -40,15 +40,21 @@ static COMP_MAP: phf::Map<&'static str, &'static str> = phf_map! {
     "A-1" => "110010",
     "M-1" => "110010",
     "D+A" => "000010",
+    "A+D" => "000010",
     "D+M" => "000010",
+    "M+D" => "000010",
     "D-A" => "010011",
     "D-M" => "010011",
     "A-D" => "000111",
     "M-D" => "000111",
     "D&A" => "000000",
+    "A&D" => "000000",
     "D&M" => "000000",
+    "M&D" => "000000",
     "D|A" => "010101",
+    "A|D" => "010101",
     "D|M" => "010101",
+    "M|D" => "010101",
 };
 
 static JUMP_MAP: phf::Map<&'static str, &'static str> = phf_map! {
@@ -154,6 +160,9 @@ mod tests {
             TestCase::new("D&M", "000000"),
             TestCase::new("D|A", "010101"),
             TestCase::new("D|M", "010101"),
+            TestCase::new("M+D", "000010"),
+            TestCase::new("M&D", "000000"),
+            TestCase::new("M|D", "010101"),
         ];
 
         test_iter(test_cases, Box::new(comp));
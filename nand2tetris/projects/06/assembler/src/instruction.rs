@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq)]
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Instruction {
     AConst(i32),
     AVar(String),
@@ -8,4 +10,63 @@ pub enum Instruction {
         jump: Option<String>,
     },
     L(String),
+    Comment(String),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AConst(val) => write!(f, "@{}", val),
+            Self::AVar(var) => write!(f, "@{}", var),
+            Self::L(lbl) => write!(f, "({})", lbl),
+            Self::Comment(text) => write!(f, "// {}", text),
+            Self::C { dest, comp, jump } => {
+                if let Some(dest) = dest {
+                    write!(f, "{}=", dest)?;
+                }
+
+                write!(f, "{}", comp)?;
+
+                if let Some(jump) = jump {
+                    write!(f, ";{}", jump)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let instructs = [
+            Instruction::AConst(7),
+            Instruction::AVar("LOOP".to_string()),
+            Instruction::L("LOOP".to_string()),
+            Instruction::C {
+                dest: Some("D".to_string()),
+                comp: "M".to_string(),
+                jump: None,
+            },
+            Instruction::C {
+                dest: None,
+                comp: "0".to_string(),
+                jump: Some("JMP".to_string()),
+            },
+            Instruction::Comment("note".to_string()),
+        ];
+        let expected = ["@7", "@LOOP", "(LOOP)", "D=M", "0;JMP", "// note"];
+
+        assert_eq!(
+            expected.to_vec(),
+            instructs
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
 }
@@ -1,3 +1,4 @@
+pub mod assemble;
 pub mod code;
 pub mod instruction;
 pub mod parser;
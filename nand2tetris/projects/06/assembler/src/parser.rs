@@ -85,7 +85,7 @@ impl Parser {
         while invalid {
             match self.lines.next() {
                 Some(line) => {
-                    curr_line = line.unwrap();
+                    curr_line = line.unwrap().trim().to_string();
                     invalid = superficial(&curr_line);
                 }
                 None => {
@@ -0,0 +1,163 @@
+use crate::syntax::{Span, Spanned};
+use std::fmt;
+
+pub type Ident = Spanned<String>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Int,
+    Char,
+    Boolean,
+    Class(String),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Char => write!(f, "char"),
+            Self::Boolean => write!(f, "boolean"),
+            Self::Class(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Class {
+    pub name: Ident,
+    pub vars: Vec<ClassVarDec>,
+    pub subroutines: Vec<SubroutineDec>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClassVarKind {
+    Static,
+    Field,
+}
+
+/// `static int x, y;` or `field ...`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassVarDec {
+    pub kind: ClassVarKind,
+    pub ty: Spanned<Type>,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubroutineKind {
+    Constructor,
+    Function,
+    Method,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubroutineDec {
+    pub kind: SubroutineKind,
+    /// `None` for `void`.
+    pub return_type: Option<Spanned<Type>>,
+    pub name: Ident,
+    pub params: Vec<Parameter>,
+    pub body: SubroutineBody,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub ty: Spanned<Type>,
+    pub name: Ident,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubroutineBody {
+    pub vars: Vec<VarDec>,
+    pub statements: Vec<Spanned<Statement>>,
+    pub span: Span,
+}
+
+/// `var int x, y;`
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDec {
+    pub ty: Spanned<Type>,
+    pub names: Vec<Ident>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Let {
+        target: Ident,
+        index: Option<Expression>,
+        value: Expression,
+    },
+    If {
+        cond: Expression,
+        then_branch: Vec<Spanned<Statement>>,
+        else_branch: Option<Vec<Spanned<Statement>>>,
+    },
+    While {
+        cond: Expression,
+        body: Vec<Spanned<Statement>>,
+    },
+    Do(SubroutineCall),
+    Return(Option<Expression>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Lt,
+    Gt,
+    Eq,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeywordConst {
+    True,
+    False,
+    Null,
+    This,
+}
+
+/// Jack has no operator precedence, so an expression is its terms in
+/// source order, evaluated left to right.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    pub first: Box<Spanned<Term>>,
+    pub rest: Vec<(Spanned<BinaryOp>, Spanned<Term>)>,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    IntConst(i16),
+    StringConst(String),
+    Keyword(KeywordConst),
+    Var(String),
+    Index(Ident, Box<Expression>),
+    Call(SubroutineCall),
+    Paren(Box<Expression>),
+    Unary(Spanned<UnaryOp>, Box<Spanned<Term>>),
+}
+
+/// `f(args)`, or `x.f(args)` where `x` is a variable or class name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubroutineCall {
+    pub receiver: Option<Ident>,
+    pub name: Ident,
+    pub args: Vec<Expression>,
+    pub span: Span,
+}
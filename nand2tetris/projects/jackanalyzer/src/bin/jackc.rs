@@ -0,0 +1,91 @@
+use assembler::assemble;
+use jackanalyzer::build;
+use jackanalyzer::check::Strictness;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use vmtranslator::code;
+use vmtranslator::translate;
+
+#[derive(StructOpt)]
+enum Args {
+    /// Compile a program directory, with the OS, to a single `.hack` file.
+    Build {
+        /// A directory of `.jack` files, one of which defines `Main.main`.
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+
+        /// A directory of OS classes, as implemented `.jack` sources or as
+        /// compiled `.vm` files. Without it, the program must define every
+        /// class it uses, `Sys` included. The program's own classes take
+        /// precedence, then sources over `.vm` files.
+        #[structopt(long, parse(from_os_str))]
+        os: Option<PathBuf>,
+
+        /// Where to write the output. Defaults to `Xxx.hack` inside the
+        /// directory `Xxx`.
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Keep `boolean`, `int` and each class apart instead of treating
+        /// them all as 16-bit words.
+        #[structopt(long)]
+        strict: bool,
+
+        /// Output format: asm or hack.
+        #[structopt(long, default_value = "hack")]
+        emit: code::Emit,
+    },
+}
+
+fn main() -> std::io::Result<()> {
+    let Args::Build {
+        dir,
+        os,
+        output,
+        strict,
+        emit,
+    } = Args::from_args();
+
+    let output = match output {
+        Some(output) => output,
+        None => {
+            // `..` has no name of its own, but the directory it means does.
+            match dir.canonicalize()?.file_name() {
+                Some(name) => dir.join(name).with_extension(emit.extension()),
+                None => {
+                    return Err(build::invalid_data(format!(
+                        "cannot name the output after {}, so pass --output",
+                        dir.display()
+                    )))
+                }
+            }
+        }
+    };
+
+    let strictness = if strict {
+        Strictness::Strict
+    } else {
+        Strictness::Permissive
+    };
+    let instructions = build::build(
+        &dir,
+        os.as_deref(),
+        strictness,
+        &translate::Options::default(),
+    )?;
+
+    let lines = match emit {
+        code::Emit::Asm => instructions.iter().map(|x| x.to_string()).collect(),
+        code::Emit::Hack => assemble::assemble(&instructions),
+    };
+
+    let mut writer = BufWriter::new(File::create(&output)?);
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use crate::ast::Class;
+use crate::check::{self, Strictness};
+use crate::codegen;
+use crate::os;
+use crate::parser::parse;
+use crate::syntax::{Span, Spanned, Token};
+use crate::token::tokenize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use vmtranslator::error::VmError;
+use vmtranslator::instruct::Instruction;
+use vmtranslator::translate;
+
+/// The file at `path`, or the files directly inside it with extension
+/// `ext`, sorted so output does not depend on directory order.
+pub fn files(path: &Path, ext: &str) -> io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = path
+        .read_dir()?
+        .filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.is_file() && x.extension().is_some_and(|x| x == ext))
+        .collect::<Vec<PathBuf>>();
+
+    files.sort();
+    Ok(files)
+}
+
+pub fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Prints each error with the line of `src` it points at.
+pub fn report<E: std::fmt::Display>(
+    path: &Path,
+    src: &str,
+    errors: &[E],
+    span: fn(&E) -> Span,
+) -> io::Error {
+    for error in errors {
+        eprintln!("error: {}:{}", path.display(), error);
+        eprintln!("{}", span(error).snippet(src));
+    }
+
+    invalid_data(format!("{} error(s) in {}", errors.len(), path.display()))
+}
+
+/// A `.jack` file that parsed.
+pub struct Source {
+    pub path: PathBuf,
+    pub src: String,
+    pub tokens: Vec<Spanned<Token>>,
+    pub class: Class,
+}
+
+impl Source {
+    /// Parses `src`, reporting any errors against `path`.
+    pub fn new(path: &Path, src: String) -> io::Result<Self> {
+        let tokens = tokenize(&src, 0).map_err(|errors| report(path, &src, &errors, |x| x.span))?;
+        let class = parse(&tokens).map_err(|errors| report(path, &src, &errors, |x| x.span))?;
+
+        Ok(Source {
+            path: path.to_path_buf(),
+            src,
+            tokens,
+            class,
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Source::new(path, fs::read_to_string(path)?)
+    }
+}
+
+/// Checks a class against the rest of the program and compiles it to the
+/// text of its `.vm` file.
+pub fn compile(
+    source: &Source,
+    classes: &check::Classes,
+    strictness: Strictness,
+) -> io::Result<String> {
+    let (path, src) = (&source.path, &source.src);
+    check::check(&source.class, classes, strictness)
+        .map_err(|errors| report(path, src, &errors, |x| x.span))?;
+    let commands =
+        codegen::compile(&source.class).map_err(|err| report(path, src, &[err], |x| x.span))?;
+
+    let mut text = vec![];
+    for cmd in commands {
+        writeln!(text, "{}", cmd)?;
+    }
+
+    Ok(String::from_utf8(text).unwrap())
+}
+
+/// Checks and compiles every class of a program, along with the OS classes
+/// it does not define itself, to `(class name, VM code)` pairs. The OS
+/// comes as `.jack` sources and as `.vm` files for classes without sources.
+pub fn modules(
+    program: Vec<Source>,
+    os_sources: Vec<Source>,
+    os_vm: Vec<(String, String)>,
+    strictness: Strictness,
+) -> io::Result<Vec<(String, String)>> {
+    let mut defined = program
+        .iter()
+        .map(|source| source.class.name.node.to_string())
+        .collect::<HashSet<String>>();
+    let os_sources = os_sources
+        .into_iter()
+        .filter(|source| defined.insert(source.class.name.node.to_string()))
+        .collect::<Vec<Source>>();
+    let sources = program
+        .into_iter()
+        .chain(os_sources)
+        .collect::<Vec<Source>>();
+
+    let mut classes = os::classes();
+    for source in &sources {
+        classes.declare(&source.class);
+    }
+
+    // Compile everything before giving up, so all errors are reported.
+    let mut modules = vec![];
+    let mut failed = 0;
+    for source in &sources {
+        match compile(source, &classes, strictness) {
+            Ok(text) => modules.push((source.class.name.node.to_string(), text)),
+            Err(_) => failed += 1,
+        }
+    }
+    if failed > 0 {
+        return Err(invalid_data(format!(
+            "{} class(es) failed to compile",
+            failed
+        )));
+    }
+
+    modules.extend(
+        os_vm
+            .into_iter()
+            .filter(|(name, _)| !defined.contains(name)),
+    );
+    Ok(modules)
+}
+
+/// Translates the VM code of a whole program to Hack instructions, starting
+/// with the bootstrap. Every function called, `Sys.init` included, must be
+/// defined.
+pub fn link(
+    modules: &[(String, String)],
+    options: &translate::Options,
+) -> Result<Vec<Instruction>, Vec<VmError>> {
+    let inputs = modules
+        .iter()
+        .map(|(name, text)| (name.as_str(), text.as_bytes()));
+    let options = translate::Options {
+        auto_bootstrap: false,
+        check_calls: true,
+        ..options.clone()
+    };
+
+    translate::translate(inputs, &options)
+}
+
+/// Compiles the program in `dir`, with the OS classes from `os` it does not
+/// define, to Hack instructions that start by calling `Sys.init`.
+pub fn build(
+    dir: &Path,
+    os: Option<&Path>,
+    strictness: Strictness,
+    options: &translate::Options,
+) -> io::Result<Vec<Instruction>> {
+    let program = files(dir, "jack")?
+        .iter()
+        .map(|path| Source::load(path))
+        .collect::<io::Result<Vec<Source>>>()?;
+    if program.is_empty() {
+        return Err(invalid_data(format!("no .jack files in {}", dir.display())));
+    }
+
+    let (os_sources, os_vm) = match os {
+        Some(os) => {
+            let sources = files(os, "jack")?
+                .iter()
+                .map(|path| Source::load(path))
+                .collect::<io::Result<Vec<Source>>>()?;
+            let mut vm = vec![];
+            for path in files(os, "vm")? {
+                // The stem names the class, so it must be usable text.
+                let name = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| {
+                        invalid_data(format!("cannot name a class after {}", path.display()))
+                    })?;
+                vm.push((name.to_string(), fs::read_to_string(&path)?));
+            }
+            (sources, vm)
+        }
+        None => (vec![], vec![]),
+    };
+
+    let modules = modules(program, os_sources, os_vm, strictness)?;
+    link(&modules, options).map_err(|errors| {
+        for error in &errors {
+            eprintln!("error: {}", error);
+        }
+        invalid_data(format!("{} error(s) in VM program", errors.len()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vmtranslator::emulator::Cpu;
+
+    fn source(src: &str) -> Source {
+        Source::new(Path::new("test.jack"), src.to_string()).unwrap()
+    }
+
+    /// A compiled OS that runs `Main.main`, with a bump allocator and
+    /// `Memory.poke`.
+    fn os_vm() -> Vec<(String, String)> {
+        vec![
+            (
+                "Sys".to_string(),
+                "function Sys.init 0\ncall Main.main 0\npop temp 0\nlabel END\ngoto END\n"
+                    .to_string(),
+            ),
+            (
+                "Memory".to_string(),
+                "function Memory.alloc 0\npush static 0\npush constant 0\neq\n\
+                 if-goto FIRST\nlabel ALLOC\npush static 0\npush static 0\n\
+                 push argument 0\nadd\npop static 0\nreturn\nlabel FIRST\n\
+                 push constant 2048\npop static 0\ngoto ALLOC\n\
+                 function Memory.poke 0\npush argument 1\npush argument 0\n\
+                 pop pointer 1\npop that 0\npush constant 0\nreturn\n"
+                    .to_string(),
+            ),
+            (
+                "Math".to_string(),
+                "function Math.max 0\npush constant 0\nreturn\n".to_string(),
+            ),
+        ]
+    }
+
+    /// A program built with the OS runs under the CPU emulator. Its own
+    /// classes replace OS classes of the same name, and OS sources replace
+    /// compiled OS classes.
+    #[test]
+    fn test_build_program() {
+        let program = vec![
+            source(
+                "class Main { function void main() { var Point p;\n\
+                 let p = Point.new(3, 4); let p = Point.new(Math.max(1, 2), 5);\n\
+                 do Memory.poke(8000, p.sum()); return; } }",
+            ),
+            source(
+                "class Point { field int x, y;\n\
+                 constructor Point new(int ax, int ay) { let x = ax; let y = ay; return this; }\n\
+                 method int sum() { return x + y; } }",
+            ),
+        ];
+        let os_sources = vec![
+            source("class Math { function int max(int a, int b) { if (a > b) { return a; } return b; } }"),
+            source("class Point { }"),
+        ];
+
+        let modules = modules(program, os_sources, os_vm(), Strictness::Strict).unwrap();
+        let names = modules
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["Main", "Point", "Math", "Sys", "Memory"], names);
+
+        let instructs = link(&modules, &translate::Options::default()).unwrap();
+        let mut cpu = Cpu::new(&instructs);
+        assert!(cpu.run(100_000));
+        assert_eq!(7, cpu.ram()[8000]);
+    }
+
+    /// Without the OS, its calls and `Sys.init` are missing.
+    #[test]
+    fn test_link_without_os() {
+        let program = vec![source(
+            "class Main { function void main() { do Output.printInt(7); return; } }",
+        )];
+        let vm = modules(program, vec![], vec![], Strictness::Permissive).unwrap();
+        let errors = link(&vm, &translate::Options::default())
+            .unwrap_err()
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            vec!["Main.vm:3: call to undefined function `Output.printInt`"],
+            errors
+        );
+
+        let program = vec![source("class Main { function void main() { return; } }")];
+        let vm = modules(program, vec![], vec![], Strictness::Permissive).unwrap();
+        assert_eq!(
+            "bootstrap calls undefined function `Sys.init`",
+            link(&vm, &translate::Options::default()).unwrap_err()[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let program = vec![source(
+            "class Main { function void main() { do Output.printStr(1); return; } }",
+        )];
+        let err = modules(program, vec![], os_vm(), Strictness::Permissive).unwrap_err();
+        assert_eq!("1 class(es) failed to compile", err.to_string());
+    }
+}
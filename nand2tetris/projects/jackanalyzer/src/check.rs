@@ -0,0 +1,792 @@
+use crate::ast::*;
+use crate::symbols::{Kind, SymbolTable};
+use crate::syntax::{Span, Spanned};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SemanticError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.msg)
+    }
+}
+
+impl std::error::Error for SemanticError {}
+
+/// How closely types must match.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Strictness {
+    /// Like the reference compiler, treat every value as a 16-bit word:
+    /// only mixing two different classes, neither of them `Array`, is an
+    /// error.
+    #[default]
+    Permissive,
+    /// `boolean`, `int` and each class are distinct types, though `int` and
+    /// `char` still mix, `null` fits any class, any object fits an `Array`
+    /// (as in `Memory.deAlloc(this)`), and conditions must be `boolean`.
+    Strict,
+}
+
+/// How a subroutine is called from outside its class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub kind: SubroutineKind,
+    /// `None` for `void`.
+    pub return_type: Option<Type>,
+    pub params: Vec<Type>,
+}
+
+impl From<&SubroutineDec> for Signature {
+    fn from(subroutine: &SubroutineDec) -> Self {
+        Signature {
+            kind: subroutine.kind,
+            return_type: subroutine.return_type.as_ref().map(|ty| ty.node.clone()),
+            params: subroutine
+                .params
+                .iter()
+                .map(|param| param.ty.node.clone())
+                .collect(),
+        }
+    }
+}
+
+/// The subroutines of the classes a program is made of. Calls into a
+/// class missing from here are taken on trust.
+#[derive(Clone, Debug, Default)]
+pub struct Classes {
+    classes: HashMap<String, HashMap<String, Signature>>,
+}
+
+impl Classes {
+    pub fn new() -> Self {
+        Classes::default()
+    }
+
+    /// Adds `class`, replacing any class of the same name.
+    pub fn declare(&mut self, class: &Class) {
+        let subroutines = class
+            .subroutines
+            .iter()
+            .map(|sub| (sub.name.node.to_string(), Signature::from(sub)))
+            .collect();
+        self.classes
+            .insert(class.name.node.to_string(), subroutines);
+    }
+
+    pub fn contains(&self, class: &str) -> bool {
+        self.classes.contains_key(class)
+    }
+
+    pub fn signature(&self, class: &str, subroutine: &str) -> Option<&Signature> {
+        self.classes.get(class)?.get(subroutine)
+    }
+}
+
+/// The type of an expression, as far as the checker can tell.
+#[derive(Clone, Debug, PartialEq)]
+enum Ty {
+    Known(Type),
+    /// `null`, which any object variable accepts.
+    Null,
+    /// An array element, or the value of a call into an unknown class.
+    Unknown,
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Known(ty) => write!(f, "`{}`", ty),
+            Self::Null => write!(f, "`null`"),
+            Self::Unknown => write!(f, "an unknown type"),
+        }
+    }
+}
+
+fn is_numeric(ty: &Ty) -> bool {
+    matches!(ty, Ty::Known(Type::Int | Type::Char) | Ty::Unknown)
+}
+
+fn is_boolean(ty: &Ty) -> bool {
+    matches!(ty, Ty::Known(Type::Boolean) | Ty::Unknown)
+}
+
+/// Whether running `statements` always ends in a `return`.
+fn returns(statements: &[Spanned<Statement>]) -> bool {
+    statements.iter().any(|statement| match &statement.node {
+        Statement::Return(_) => true,
+        Statement::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => returns(then_branch) && returns(else_branch),
+        _ => false,
+    })
+}
+
+/// Checks one class against the program it belongs to.
+pub struct Checker<'a> {
+    class: &'a Class,
+    classes: &'a Classes,
+    /// This class's own subroutines, which may be newer than `classes`.
+    own: HashMap<&'a str, Signature>,
+    strictness: Strictness,
+    symbols: SymbolTable,
+    subroutine: Option<&'a SubroutineDec>,
+    errors: Vec<SemanticError>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(class: &'a Class, classes: &'a Classes, strictness: Strictness) -> Self {
+        Checker {
+            class,
+            classes,
+            own: HashMap::new(),
+            strictness,
+            symbols: SymbolTable::new(),
+            subroutine: None,
+            errors: vec![],
+        }
+    }
+
+    fn error(&mut self, span: Span, msg: String) {
+        self.errors.push(SemanticError { span, msg });
+    }
+
+    fn strict(&self) -> bool {
+        self.strictness == Strictness::Strict
+    }
+
+    fn signature(&self, class: &str, subroutine: &str) -> Option<&Signature> {
+        if class == self.class.name.node {
+            self.own.get(subroutine)
+        } else {
+            self.classes.signature(class, subroutine)
+        }
+    }
+
+    fn is_known(&self, class: &str) -> bool {
+        class == self.class.name.node || self.classes.contains(class)
+    }
+
+    /// Whether a value of type `from` can be stored where `to` is expected.
+    fn assignable(&self, to: &Type, from: &Ty) -> bool {
+        let from = match from {
+            Ty::Known(from) => from,
+            Ty::Null => return !self.strict() || matches!(to, Type::Class(_)),
+            Ty::Unknown => return true,
+        };
+
+        match (to, from) {
+            _ if to == from => true,
+            (Type::Int | Type::Char, Type::Int | Type::Char) => true,
+            (Type::Class(to), Type::Class(_)) if to == "Array" => true,
+            (Type::Class(_), Type::Class(from)) if !self.strict() => from == "Array",
+            _ => !self.strict(),
+        }
+    }
+
+    fn expect_type(&mut self, span: Span, to: &Type, from: &Ty, what: &str) {
+        if !self.assignable(to, from) {
+            self.error(span, format!("{} must be `{}`, found {}", what, to, from));
+        }
+    }
+
+    /// The kind and type of a variable, if it is declared and usable here.
+    fn variable(&mut self, name: &str, span: Span) -> Option<(Kind, Type)> {
+        let var = match self.symbols.lookup(name) {
+            Some(var) => (var.kind, var.ty.clone()),
+            None => {
+                self.error(span, format!("undefined variable `{}`", name));
+                return None;
+            }
+        };
+
+        if let Some(sub) = self.subroutine {
+            if var.0 == Kind::Field && sub.kind == SubroutineKind::Function {
+                self.error(
+                    span,
+                    format!(
+                        "field `{}` cannot be used in function `{}`, which has no `this`",
+                        name, sub.name.node
+                    ),
+                );
+            }
+        }
+
+        Some(var)
+    }
+
+    pub fn check(mut self) -> Result<(), Vec<SemanticError>> {
+        let class = self.class;
+
+        for var in &class.vars {
+            let kind = match var.kind {
+                ClassVarKind::Static => Kind::Static,
+                ClassVarKind::Field => Kind::Field,
+            };
+            for name in &var.names {
+                if !self.symbols.define(&name.node, var.ty.node.clone(), kind) {
+                    self.error(name.span, format!("`{}` is already declared", name.node));
+                }
+            }
+        }
+
+        for sub in &class.subroutines {
+            if self.own.contains_key(sub.name.node.as_str()) {
+                self.error(
+                    sub.name.span,
+                    format!("subroutine `{}` is already declared", sub.name.node),
+                );
+            } else {
+                self.own.insert(&sub.name.node, Signature::from(sub));
+            }
+        }
+
+        for sub in &class.subroutines {
+            self.subroutine(sub);
+        }
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn subroutine(&mut self, sub: &'a SubroutineDec) {
+        self.symbols.start_subroutine();
+        self.subroutine = Some(sub);
+
+        let locals = sub.body.vars.iter().flat_map(|var| {
+            var.names
+                .iter()
+                .map(move |name| (name, &var.ty.node, Kind::Var))
+        });
+        for (name, ty, kind) in sub
+            .params
+            .iter()
+            .map(|param| (&param.name, &param.ty.node, Kind::Argument))
+            .chain(locals)
+        {
+            if !self.symbols.define(&name.node, ty.clone(), kind) {
+                self.error(name.span, format!("`{}` is already declared", name.node));
+            }
+        }
+
+        self.statements(&sub.body.statements);
+
+        if !returns(&sub.body.statements) {
+            self.error(
+                sub.name.span,
+                format!("`{}` can reach its end without a `return`", sub.name.node),
+            );
+        }
+    }
+
+    fn statements(&mut self, statements: &[Spanned<Statement>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn condition(&mut self, cond: &Expression) {
+        let ty = self.expression(cond);
+        if self.strict() && !is_boolean(&ty) {
+            self.error(
+                cond.span,
+                format!("condition must be `boolean`, found {}", ty),
+            );
+        }
+    }
+
+    fn statement(&mut self, statement: &Spanned<Statement>) {
+        match &statement.node {
+            Statement::Let {
+                target,
+                index,
+                value,
+            } => {
+                let var = self.variable(&target.node, target.span);
+                let value_ty = self.expression(value);
+                match (var, index) {
+                    (Some((_, ty)), None) => {
+                        let what = format!("value assigned to `{}`", target.node);
+                        self.expect_type(value.span, &ty, &value_ty, &what);
+                    }
+                    (var, Some(index)) => {
+                        if let Some((_, ty)) = var {
+                            self.index(target, &ty, index);
+                        } else {
+                            self.expression(index);
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+            Statement::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.condition(cond);
+                self.statements(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statements(else_branch);
+                }
+            }
+            Statement::While { cond, body } => {
+                self.condition(cond);
+                self.statements(body);
+            }
+            Statement::Do(call) => {
+                self.subroutine_call(call);
+            }
+            Statement::Return(value) => {
+                let sub = self.subroutine.unwrap();
+                let value_ty = value.as_ref().map(|value| self.expression(value));
+                match (&sub.return_type, value, value_ty) {
+                    (Some(ty), Some(value), Some(value_ty)) => {
+                        let what = format!("value returned from `{}`", sub.name.node);
+                        self.expect_type(value.span, &ty.node, &value_ty, &what);
+                    }
+                    (Some(ty), None, _) => self.error(
+                        statement.span,
+                        format!("`{}` must return a `{}` value", sub.name.node, ty.node),
+                    ),
+                    (None, Some(value), _) => self.error(
+                        value.span,
+                        format!("`{}` is void, so it cannot return a value", sub.name.node),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Checks `name[index]` where `name` has type `ty`.
+    fn index(&mut self, name: &Ident, ty: &Type, index: &Expression) {
+        if self.strict() && *ty != Type::Class("Array".to_string()) {
+            self.error(
+                name.span,
+                format!("`{}` is `{}`, not an `Array`", name.node, ty),
+            );
+        }
+
+        let index_ty = self.expression(index);
+        if self.strict() && !is_numeric(&index_ty) {
+            self.error(
+                index.span,
+                format!("array index must be `int`, found {}", index_ty),
+            );
+        }
+    }
+
+    /// Checks a call and returns its signature, if the callee is known.
+    fn subroutine_call(&mut self, call: &SubroutineCall) -> Option<Signature> {
+        let name = &call.name.node;
+
+        let (class_name, on_object) = match &call.receiver {
+            None => (self.class.name.node.to_string(), None),
+            Some(receiver) => match self.symbols.lookup(&receiver.node) {
+                Some(var) => match &var.ty {
+                    Type::Class(class_name) => (class_name.to_string(), Some(receiver)),
+                    _ => {
+                        self.error(
+                            receiver.span,
+                            format!(
+                                "`{}` is not an object, so `{}` cannot be called on it",
+                                receiver.node, name
+                            ),
+                        );
+                        return None;
+                    }
+                },
+                None => (receiver.node.to_string(), None),
+            },
+        };
+        let full_name = format!("{}.{}", class_name, name);
+
+        let sig = self.signature(&class_name, name).cloned();
+        let sig = match sig {
+            Some(sig) => sig,
+            None => {
+                if self.is_known(&class_name) {
+                    self.error(
+                        call.name.span,
+                        format!("class `{}` has no subroutine `{}`", class_name, name),
+                    );
+                }
+                for arg in &call.args {
+                    self.expression(arg);
+                }
+                return None;
+            }
+        };
+
+        let sub = self.subroutine.unwrap();
+        match (sig.kind, &call.receiver, on_object) {
+            // `f()` on `this`.
+            (SubroutineKind::Method, None, _) if sub.kind == SubroutineKind::Function => self
+                .error(
+                    call.name.span,
+                    format!(
+                        "method `{}` cannot be called from function `{}`, which has no `this`",
+                        name, sub.name.node
+                    ),
+                ),
+            (SubroutineKind::Method, Some(_), None) => self.error(
+                call.span,
+                format!("method `{}` must be called on an object", full_name),
+            ),
+            (_, _, Some(receiver)) if sig.kind != SubroutineKind::Method => self.error(
+                call.span,
+                format!(
+                    "`{}` is not a method, so it cannot be called on `{}`",
+                    full_name, receiver.node
+                ),
+            ),
+            _ => {}
+        }
+
+        if sig.params.len() != call.args.len() {
+            self.error(
+                call.span,
+                format!(
+                    "`{}` takes {} argument(s), but {} were given",
+                    full_name,
+                    sig.params.len(),
+                    call.args.len()
+                ),
+            );
+        }
+
+        for (i, arg) in call.args.iter().enumerate() {
+            let arg_ty = self.expression(arg);
+            if let Some(param) = sig.params.get(i) {
+                let what = format!("argument {} of `{}`", i + 1, full_name);
+                self.expect_type(arg.span, param, &arg_ty, &what);
+            }
+        }
+
+        Some(sig)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> Ty {
+        let mut ty = self.term(&expr.first);
+
+        for (op, term) in &expr.rest {
+            let rhs = self.term(term);
+            let (operands_ok, result) = match op.node {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
+                    (is_numeric(&ty) && is_numeric(&rhs), Type::Int)
+                }
+                BinaryOp::Lt | BinaryOp::Gt => (is_numeric(&ty) && is_numeric(&rhs), Type::Boolean),
+                BinaryOp::And | BinaryOp::Or if is_boolean(&ty) && is_boolean(&rhs) => {
+                    (true, Type::Boolean)
+                }
+                BinaryOp::And | BinaryOp::Or => (is_numeric(&ty) && is_numeric(&rhs), Type::Int),
+                BinaryOp::Eq => {
+                    let comparable = match (&ty, &rhs) {
+                        (Ty::Known(lhs), rhs) => self.assignable(lhs, rhs),
+                        (lhs, Ty::Known(rhs)) => self.assignable(rhs, lhs),
+                        _ => true,
+                    };
+                    (comparable, Type::Boolean)
+                }
+            };
+
+            if self.strict() && !operands_ok {
+                self.error(
+                    op.span,
+                    format!("operator cannot be applied to {} and {}", ty, rhs),
+                );
+            }
+            ty = Ty::Known(result);
+        }
+
+        ty
+    }
+
+    fn term(&mut self, term: &Spanned<Term>) -> Ty {
+        match &term.node {
+            Term::IntConst(_) => Ty::Known(Type::Int),
+            Term::StringConst(_) => Ty::Known(Type::Class("String".to_string())),
+            Term::Keyword(KeywordConst::True | KeywordConst::False) => Ty::Known(Type::Boolean),
+            Term::Keyword(KeywordConst::Null) => Ty::Null,
+            Term::Keyword(KeywordConst::This) => {
+                let sub = self.subroutine.unwrap();
+                if sub.kind == SubroutineKind::Function {
+                    self.error(
+                        term.span,
+                        format!("`this` cannot be used in function `{}`", sub.name.node),
+                    );
+                }
+                Ty::Known(Type::Class(self.class.name.node.to_string()))
+            }
+            Term::Var(name) => match self.variable(name, term.span) {
+                Some((_, ty)) => Ty::Known(ty),
+                None => Ty::Unknown,
+            },
+            Term::Index(name, index) => {
+                match self.variable(&name.node, name.span) {
+                    Some((_, ty)) => self.index(name, &ty, index),
+                    None => {
+                        self.expression(index);
+                    }
+                }
+                Ty::Unknown
+            }
+            Term::Call(call) => match self.subroutine_call(call) {
+                Some(Signature {
+                    return_type: Some(ty),
+                    ..
+                }) => Ty::Known(ty),
+                Some(Signature {
+                    return_type: None, ..
+                }) => {
+                    self.error(
+                        call.span,
+                        format!("`{}` is void, so it has no value", call.name.node),
+                    );
+                    Ty::Unknown
+                }
+                None => Ty::Unknown,
+            },
+            Term::Paren(expr) => self.expression(expr),
+            Term::Unary(op, inner) => {
+                let ty = self.term(inner);
+                let (operand_ok, result) = match op.node {
+                    UnaryOp::Neg => (is_numeric(&ty), Ty::Known(Type::Int)),
+                    UnaryOp::Not if is_boolean(&ty) => (true, Ty::Known(Type::Boolean)),
+                    UnaryOp::Not => (is_numeric(&ty), Ty::Known(Type::Int)),
+                };
+                if self.strict() && !operand_ok {
+                    self.error(op.span, format!("operator cannot be applied to {}", ty));
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Checks a class against the program it belongs to, returning every error
+/// found if there are any.
+pub fn check(
+    class: &Class,
+    classes: &Classes,
+    strictness: Strictness,
+) -> Result<(), Vec<SemanticError>> {
+    Checker::new(class, classes, strictness).check()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os;
+    use crate::parser::parse;
+    use crate::token::tokenize;
+    use std::fs;
+    use std::path::Path;
+
+    fn parse_str(input: &str) -> Class {
+        parse(&tokenize(input, 0).unwrap()).unwrap()
+    }
+
+    fn errors_with(input: &str, others: &[&str], strictness: Strictness) -> Vec<String> {
+        let mut classes = Classes::new();
+        for other in others {
+            classes.declare(&parse_str(other));
+        }
+
+        match check(&parse_str(input), &classes, strictness) {
+            Ok(()) => vec![],
+            Err(errors) => errors.iter().map(|err| err.to_string()).collect(),
+        }
+    }
+
+    fn errors(input: &str) -> Vec<String> {
+        errors_with(input, &[], Strictness::Permissive)
+    }
+
+    #[test]
+    fn test_declarations() {
+        let input = "class A { field int x; static char x;\n\
+                     function void f(int a, int a) { var int b; var boolean b; return; }\n\
+                     function void f() { let y = 1; let z[0] = y; return; } }";
+        assert_eq!(
+            vec![
+                "1:36: `x` is already declared",
+                "3:15: subroutine `f` is already declared",
+                "2:28: `a` is already declared",
+                "2:56: `b` is already declared",
+                "3:25: undefined variable `y`",
+                "3:36: undefined variable `z`",
+                "3:43: undefined variable `y`",
+            ],
+            errors(input)
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        let point = "class Point { field int x;\n\
+                     constructor Point new(int ax) { let x = ax; return this; }\n\
+                     method int getX() { return x; }\n\
+                     function void reset() { return; } }";
+        let input = "class Main { function void main() { var Point p; var int n;\n\
+                     let p = Point.new(1, 2); let n = Point.getX();\n\
+                     do p.reset(); do p.move(); do n.getX(); do Output.printInt(1);\n\
+                     do helper(); do main(); let n = Point.reset(); return; }\n\
+                     method void helper() { do helper(); return; } }";
+        assert_eq!(
+            vec![
+                "2:9: `Point.new` takes 1 argument(s), but 2 were given",
+                "2:34: method `Point.getX` must be called on an object",
+                "3:4: `Point.reset` is not a method, so it cannot be called on `p`",
+                "3:20: class `Point` has no subroutine `move`",
+                "3:31: `n` is not an object, so `getX` cannot be called on it",
+                "4:4: method `helper` cannot be called from function `main`, which has no `this`",
+                "4:33: `reset` is void, so it has no value",
+            ],
+            errors_with(input, &[point], Strictness::Permissive)
+        );
+    }
+
+    #[test]
+    fn test_this_in_function() {
+        let input = "class A { field int x;\n\
+                     function A f() { let x = 1; return this; } }";
+        assert_eq!(
+            vec![
+                "2:22: field `x` cannot be used in function `f`, which has no `this`",
+                "2:36: `this` cannot be used in function `f`",
+            ],
+            errors(input)
+        );
+    }
+
+    #[test]
+    fn test_returns() {
+        let input = "class A {\n\
+                     function int f(boolean b) { if (b) { return 1; } }\n\
+                     function int g(boolean b) { if (b) { return 1; } else { return 2; } }\n\
+                     function void h() { while (true) { return; } }\n\
+                     function int i() { return; }\n\
+                     function void j() { return 1; } }";
+        assert_eq!(
+            vec![
+                "2:14: `f` can reach its end without a `return`",
+                "4:15: `h` can reach its end without a `return`",
+                "5:20: `i` must return a `int` value",
+                "6:28: `j` is void, so it cannot return a value",
+            ],
+            errors(input)
+        );
+    }
+
+    #[test]
+    fn test_strictness() {
+        let input =
+            "class A { function void f(int i, char c, boolean b, A a, Array arr, String s) {\n\
+                     let i = c + 1; let b = i; let a = arr; let arr = i; let s = a;\n\
+                     if (i) { let a = null; } let b = ~b & (i < 2); let i = ~i | b;\n\
+                     let i = arr[b]; let i = s[0]; do A.f(c, i, 0, null, 0, \"x\");\n\
+                     return; } }";
+        assert_eq!(
+            vec!["2:61: value assigned to `s` must be `String`, found `A`"],
+            errors(input)
+        );
+        assert_eq!(
+            vec![
+                "2:24: value assigned to `b` must be `boolean`, found `int`",
+                "2:35: value assigned to `a` must be `A`, found `Array`",
+                "2:50: value assigned to `arr` must be `Array`, found `int`",
+                "2:61: value assigned to `s` must be `String`, found `A`",
+                "3:5: condition must be `boolean`, found `int`",
+                "3:59: operator cannot be applied to `int` and `boolean`",
+                "4:13: array index must be `int`, found `boolean`",
+                "4:25: `s` is `String`, not an `Array`",
+                "4:44: argument 3 of `A.f` must be `boolean`, found `int`",
+                "4:53: argument 5 of `A.f` must be `Array`, found `int`",
+            ],
+            errors_with(input, &[], Strictness::Strict)
+        );
+    }
+
+    /// Objects are freed through `Array` parameters, so any of them passes
+    /// as one, but an `Array` does not pass as another class.
+    #[test]
+    fn test_strict_array() {
+        let input = "class A { function void free(Array o) { return; }\n\
+                     method void dispose() { var Array arr; var A a; let arr = a;\n\
+                     do A.free(this); do A.free(\"x\"); let a = arr; return; } }";
+        assert_eq!(
+            vec!["3:42: value assigned to `a` must be `A`, found `Array`"],
+            errors_with(input, &[], Strictness::Strict)
+        );
+    }
+
+    #[test]
+    fn test_os_calls() {
+        let input = "class Main { function void main() { var String s; var int n;\n\
+                     let s = \"abc\"; let n = s.length(); do Output.printString(s, n);\n\
+                     do Output.printStr(s); do Screen.drawLine(0, 0, 1); let s = Keyboard.readLine();\n\
+                     do Memory.deAlloc(s); let s = Memory.alloc(2); return; } }";
+        let classes = os::classes();
+        let errors = check(&parse_str(input), &classes, Strictness::Strict)
+            .unwrap_err()
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            vec![
+                "2:39: `Output.printString` takes 1 argument(s), but 2 were given",
+                "3:11: class `Output` has no subroutine `printStr`",
+                "3:27: `Screen.drawLine` takes 4 argument(s), but 3 were given",
+                "3:61: `Keyboard.readLine` takes 1 argument(s), but 0 were given",
+                "4:31: value assigned to `s` must be `String`, found `Array`",
+            ],
+            errors
+        );
+    }
+
+    /// The programs of projects 09 and 11 check cleanly against their own
+    /// classes and the OS.
+    #[test]
+    fn test_check_sources() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut dirs = vec![root.join("09"), root.join("11")];
+        let mut count = 0;
+
+        while let Some(dir) = dirs.pop() {
+            let mut classes = vec![];
+            for entry in fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "jack") {
+                    let tokens = tokenize(&fs::read_to_string(&path).unwrap(), 0).unwrap();
+                    classes.push((path, parse(&tokens).unwrap()));
+                }
+            }
+
+            let mut program = os::classes();
+            for (_, class) in &classes {
+                program.declare(class);
+            }
+            for (path, class) in &classes {
+                if let Err(errors) = check(class, &program, Strictness::Permissive) {
+                    panic!("{}:{}", path.display(), errors[0]);
+                }
+                count += 1;
+            }
+        }
+
+        assert!(count > 0);
+    }
+}
@@ -0,0 +1,511 @@
+use crate::ast::*;
+use crate::symbols::{Kind, SymbolTable};
+use crate::syntax::{Span, Spanned};
+use std::collections::HashMap;
+use std::fmt;
+use vmtranslator::command::{ArithmeticOp, Command, Segment};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompileError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.msg)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+type CompileResult<T> = Result<T, CompileError>;
+
+fn constant(val: usize) -> Command {
+    Command::Push(Segment::Constant(val as i16))
+}
+
+fn arithmetic(op: ArithmeticOp) -> Command {
+    Command::Arithmetic(op)
+}
+
+/// Lowers one class to VM commands.
+pub struct CodeGen<'a> {
+    class: &'a Class,
+    symbols: SymbolTable,
+    subroutine_kinds: HashMap<&'a str, SubroutineKind>,
+    commands: Vec<Command>,
+    label_cnt: usize,
+}
+
+impl<'a> CodeGen<'a> {
+    pub fn new(class: &'a Class) -> Self {
+        CodeGen {
+            class,
+            symbols: SymbolTable::new(),
+            subroutine_kinds: class
+                .subroutines
+                .iter()
+                .map(|sub| (sub.name.node.as_str(), sub.kind))
+                .collect(),
+            commands: vec![],
+            label_cnt: 0,
+        }
+    }
+
+    fn emit(&mut self, cmd: Command) {
+        self.commands.push(cmd);
+    }
+
+    /// A fresh label number, unique within the subroutine.
+    fn next_label(&mut self) -> usize {
+        self.label_cnt += 1;
+        self.label_cnt - 1
+    }
+
+    fn segment(&self, kind: Kind, index: usize) -> Segment {
+        let index = index as i16;
+        match kind {
+            Kind::Static => Segment::Static(self.class.name.node.to_string(), index),
+            Kind::Field => Segment::This(index),
+            Kind::Argument => Segment::Argument(index),
+            Kind::Var => Segment::Local(index),
+        }
+    }
+
+    fn variable(&self, name: &Ident) -> CompileResult<Segment> {
+        match self.symbols.lookup(&name.node) {
+            Some(var) => Ok(self.segment(var.kind, var.index)),
+            None => Err(CompileError {
+                span: name.span,
+                msg: format!("undefined variable `{}`", name.node),
+            }),
+        }
+    }
+
+    pub fn compile(mut self) -> CompileResult<Vec<Command>> {
+        let class = self.class;
+
+        for var in &class.vars {
+            let kind = match var.kind {
+                ClassVarKind::Static => Kind::Static,
+                ClassVarKind::Field => Kind::Field,
+            };
+            for name in &var.names {
+                self.symbols.define(&name.node, var.ty.node.clone(), kind);
+            }
+        }
+
+        for subroutine in &class.subroutines {
+            self.subroutine(subroutine)?;
+        }
+
+        Ok(self.commands)
+    }
+
+    fn subroutine(&mut self, subroutine: &SubroutineDec) -> CompileResult<()> {
+        self.symbols.start_subroutine();
+        self.label_cnt = 0;
+
+        if subroutine.kind == SubroutineKind::Method {
+            let ty = Type::Class(self.class.name.node.to_string());
+            self.symbols.define("this", ty, Kind::Argument);
+        }
+        for param in &subroutine.params {
+            self.symbols
+                .define(&param.name.node, param.ty.node.clone(), Kind::Argument);
+        }
+        for var in &subroutine.body.vars {
+            for name in &var.names {
+                self.symbols
+                    .define(&name.node, var.ty.node.clone(), Kind::Var);
+            }
+        }
+
+        self.emit(Command::Function(
+            format!("{}.{}", self.class.name.node, subroutine.name.node),
+            self.symbols.var_count(Kind::Var),
+        ));
+
+        match subroutine.kind {
+            SubroutineKind::Constructor => {
+                self.emit(constant(self.symbols.var_count(Kind::Field)));
+                self.emit(Command::Call("Memory.alloc".to_string(), 1));
+                self.emit(Command::Pop(Segment::Pointer(0)));
+            }
+            SubroutineKind::Method => {
+                self.emit(Command::Push(Segment::Argument(0)));
+                self.emit(Command::Pop(Segment::Pointer(0)));
+            }
+            SubroutineKind::Function => {}
+        }
+
+        self.statements(&subroutine.body.statements)
+    }
+
+    fn statements(&mut self, statements: &[Spanned<Statement>]) -> CompileResult<()> {
+        for statement in statements {
+            self.statement(&statement.node)?;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> CompileResult<()> {
+        match statement {
+            Statement::Let {
+                target,
+                index: None,
+                value,
+            } => {
+                let segment = self.variable(target)?;
+                self.expression(value)?;
+                self.emit(Command::Pop(segment));
+            }
+            Statement::Let {
+                target,
+                index: Some(index),
+                value,
+            } => {
+                // The value may itself index an array, so keep it in temp 0
+                // until `that` points at the target.
+                let segment = self.variable(target)?;
+                self.emit(Command::Push(segment));
+                self.expression(index)?;
+                self.emit(arithmetic(ArithmeticOp::Add));
+                self.expression(value)?;
+                self.emit(Command::Pop(Segment::Temp(0)));
+                self.emit(Command::Pop(Segment::Pointer(1)));
+                self.emit(Command::Push(Segment::Temp(0)));
+                self.emit(Command::Pop(Segment::That(0)));
+            }
+            Statement::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let n = self.next_label();
+                let else_label = format!("IF_ELSE{}", n);
+                let end_label = format!("IF_END{}", n);
+
+                self.expression(cond)?;
+                self.emit(arithmetic(ArithmeticOp::Not));
+                self.emit(Command::IfGoto(else_label.to_string()));
+                self.statements(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        self.emit(Command::Goto(end_label.to_string()));
+                        self.emit(Command::Label(else_label));
+                        self.statements(else_branch)?;
+                        self.emit(Command::Label(end_label));
+                    }
+                    None => self.emit(Command::Label(else_label)),
+                }
+            }
+            Statement::While { cond, body } => {
+                let n = self.next_label();
+                let exp_label = format!("WHILE_EXP{}", n);
+                let end_label = format!("WHILE_END{}", n);
+
+                self.emit(Command::Label(exp_label.to_string()));
+                self.expression(cond)?;
+                self.emit(arithmetic(ArithmeticOp::Not));
+                self.emit(Command::IfGoto(end_label.to_string()));
+                self.statements(body)?;
+                self.emit(Command::Goto(exp_label));
+                self.emit(Command::Label(end_label));
+            }
+            Statement::Do(call) => {
+                self.subroutine_call(call)?;
+                self.emit(Command::Pop(Segment::Temp(0)));
+            }
+            Statement::Return(value) => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => self.emit(constant(0)),
+                }
+                self.emit(Command::Return);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn subroutine_call(&mut self, call: &SubroutineCall) -> CompileResult<()> {
+        let (name, arg_cnt) = match &call.receiver {
+            // `f()` calls a method on `this` unless `f` is a function or
+            // constructor of this class.
+            None => {
+                let class_name = &self.class.name.node;
+                match self.subroutine_kinds.get(call.name.node.as_str()) {
+                    Some(SubroutineKind::Method) | None => {
+                        self.emit(Command::Push(Segment::Pointer(0)));
+                        (format!("{}.{}", class_name, call.name.node), 1)
+                    }
+                    Some(_) => (format!("{}.{}", class_name, call.name.node), 0),
+                }
+            }
+            // `x.f()` calls a method on `x` if it is a variable, or else the
+            // function `f` of class `x`.
+            Some(receiver) => match self.symbols.lookup(&receiver.node).cloned() {
+                Some(var) => {
+                    let class_name = match &var.ty {
+                        Type::Class(name) => name.to_string(),
+                        _ => {
+                            return Err(CompileError {
+                                span: receiver.span,
+                                msg: format!(
+                                    "`{}` is not an object, so `{}` cannot be called on it",
+                                    receiver.node, call.name.node
+                                ),
+                            })
+                        }
+                    };
+                    self.emit(Command::Push(self.segment(var.kind, var.index)));
+                    (format!("{}.{}", class_name, call.name.node), 1)
+                }
+                None => (format!("{}.{}", receiver.node, call.name.node), 0),
+            },
+        };
+
+        for arg in &call.args {
+            self.expression(arg)?;
+        }
+        self.emit(Command::Call(name, arg_cnt + call.args.len()));
+
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: &Expression) -> CompileResult<()> {
+        self.term(&expr.first)?;
+
+        for (op, term) in &expr.rest {
+            self.term(term)?;
+            self.emit(match op.node {
+                BinaryOp::Add => arithmetic(ArithmeticOp::Add),
+                BinaryOp::Sub => arithmetic(ArithmeticOp::Subtract),
+                BinaryOp::Mul => Command::Call("Math.multiply".to_string(), 2),
+                BinaryOp::Div => Command::Call("Math.divide".to_string(), 2),
+                BinaryOp::And => arithmetic(ArithmeticOp::And),
+                BinaryOp::Or => arithmetic(ArithmeticOp::Or),
+                BinaryOp::Lt => arithmetic(ArithmeticOp::LessThan),
+                BinaryOp::Gt => arithmetic(ArithmeticOp::GreaterThan),
+                BinaryOp::Eq => arithmetic(ArithmeticOp::Equal),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn term(&mut self, term: &Spanned<Term>) -> CompileResult<()> {
+        match &term.node {
+            Term::IntConst(val) => self.emit(Command::Push(Segment::Constant(*val))),
+            Term::StringConst(text) => {
+                self.emit(constant(text.chars().count()));
+                self.emit(Command::Call("String.new".to_string(), 1));
+                for c in text.chars() {
+                    self.emit(constant(c as usize));
+                    self.emit(Command::Call("String.appendChar".to_string(), 2));
+                }
+            }
+            Term::Keyword(KeywordConst::True) => {
+                self.emit(constant(0));
+                self.emit(arithmetic(ArithmeticOp::Not));
+            }
+            Term::Keyword(KeywordConst::False) | Term::Keyword(KeywordConst::Null) => {
+                self.emit(constant(0))
+            }
+            Term::Keyword(KeywordConst::This) => self.emit(Command::Push(Segment::Pointer(0))),
+            Term::Var(name) => {
+                let segment = self.variable(&Spanned {
+                    node: name.to_string(),
+                    span: term.span,
+                })?;
+                self.emit(Command::Push(segment));
+            }
+            Term::Index(name, index) => {
+                let segment = self.variable(name)?;
+                self.emit(Command::Push(segment));
+                self.expression(index)?;
+                self.emit(arithmetic(ArithmeticOp::Add));
+                self.emit(Command::Pop(Segment::Pointer(1)));
+                self.emit(Command::Push(Segment::That(0)));
+            }
+            Term::Call(call) => self.subroutine_call(call)?,
+            Term::Paren(expr) => self.expression(expr)?,
+            Term::Unary(op, inner) => {
+                self.term(inner)?;
+                self.emit(arithmetic(match op.node {
+                    UnaryOp::Neg => ArithmeticOp::Negate,
+                    UnaryOp::Not => ArithmeticOp::Not,
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Compiles a class to the commands of its `.vm` file.
+pub fn compile(class: &Class) -> CompileResult<Vec<Command>> {
+    CodeGen::new(class).compile()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::token::tokenize;
+    use std::fs;
+    use std::path::Path;
+    use vmtranslator::interpreter::Vm;
+
+    fn compile_str(input: &str) -> CompileResult<Vec<Command>> {
+        compile(&parse(&tokenize(input, 0).unwrap()).unwrap())
+    }
+
+    fn vm_text(input: &str) -> String {
+        compile_str(input)
+            .unwrap()
+            .iter()
+            .map(|cmd| format!("{}\n", cmd))
+            .collect()
+    }
+
+    /// Just enough of the OS to run the tests: a bump allocator and
+    /// repeated-addition multiply.
+    const OS: [&str; 3] = [
+        "class Sys { function void init() { do Main.main(); return; } }",
+        "class Memory { static int free;\n\
+           function int alloc(int size) { var int p;\n\
+             if (free = 0) { let free = 2048; }\n\
+             let p = free; let free = free + size; return p; } }",
+        "class Math { function int multiply(int x, int y) { var int sum;\n\
+           while (y > 0) { let sum = sum + x; let y = y - 1; } return sum; } }",
+    ];
+
+    /// Runs `Sys.init` over the stubs and `sources`, returning RAM from 8000
+    /// on, where tests store results.
+    fn run(sources: &[&str]) -> Vec<i16> {
+        let mut program = vec![
+            Command::Call("Sys.init".to_string(), 0),
+            Command::Label("END".to_string()),
+            Command::Goto("END".to_string()),
+        ];
+        for source in OS.iter().chain(sources) {
+            program.append(&mut compile_str(source).unwrap());
+        }
+
+        let mut vm = Vm::new(&program);
+        vm.ram_mut()[0] = 256;
+        assert!(vm.run(1_000_000));
+        vm.ram()[8000..8010].to_vec()
+    }
+
+    #[test]
+    fn test_function() {
+        let input = "class Main { function int f(int a) { var int x; let x = a + 1; return x; } }";
+        assert_eq!(
+            "function Main.f 1\npush argument 0\npush constant 1\nadd\npop local 0\n\
+             push local 0\nreturn\n",
+            vm_text(input)
+        );
+    }
+
+    #[test]
+    fn test_constructor_and_method() {
+        let input = "class P { field int x, y; static int n;\n\
+                     constructor P new() { let n = n + 1; return this; }\n\
+                     method int getY() { return y; } }";
+        assert_eq!(
+            "function P.new 0\npush constant 2\ncall Memory.alloc 1\npop pointer 0\n\
+             push static 0\npush constant 1\nadd\npop static 0\npush pointer 0\nreturn\n\
+             function P.getY 0\npush argument 0\npop pointer 0\npush this 1\nreturn\n",
+            vm_text(input)
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        let input = "class A { field B b;\n\
+                     method void m() { do f(); do g(); do b.h(1); do Output.println(); return; }\n\
+                     function void f() { return; }\n\
+                     method void g() { return; } }";
+        let text = vm_text(input);
+
+        assert!(text.contains("call A.f 0\npop temp 0\npush pointer 0\ncall A.g 1\n"));
+        assert!(text.contains("push this 0\npush constant 1\ncall B.h 2\n"));
+        assert!(text.contains("call Output.println 0\n"));
+    }
+
+    #[test]
+    fn test_string_constant() {
+        let input = "class A { function String f() { return \"Hi\"; } }";
+        assert_eq!(
+            "function A.f 0\npush constant 2\ncall String.new 1\npush constant 72\n\
+             call String.appendChar 2\npush constant 105\ncall String.appendChar 2\nreturn\n",
+            vm_text(input)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = compile_str("class A { function void f() { let y = 1; return; } }").unwrap_err();
+        assert_eq!("1:35: undefined variable `y`", err.to_string());
+
+        let err =
+            compile_str("class A { function void f(int x) { do x.g(); return; } }").unwrap_err();
+        assert_eq!(
+            "`x` is not an object, so `g` cannot be called on it",
+            err.msg
+        );
+    }
+
+    #[test]
+    fn test_run_arrays_and_control_flow() {
+        let main = "class Main {\n\
+            function void main() { var Array out, a; var int i;\n\
+              let out = 8000; let a = Memory.alloc(5);\n\
+              while (i < 5) { let a[i] = i * i; let i = i + 1; }\n\
+              let out[0] = a[4]; let out[a[1]] = a[a[2]];\n\
+              if (a[3] = 9) { let out[2] = true; } else { let out[2] = 7; }\n\
+              if (~(i = 5)) { let out[3] = 1; }\n\
+              let out[4] = -(2 * 3) + (10 - 4);\n\
+              return; } }";
+
+        assert_eq!(vec![16, 16, -1, 0, 0], run(&[main])[..5].to_vec());
+    }
+
+    #[test]
+    fn test_run_objects() {
+        let main = "class Main {\n\
+            function void main() { var Array out; var Counter c, d;\n\
+              let out = 8000; let c = Counter.new(10); let d = Counter.new(20);\n\
+              do c.add(5); do d.add(1); do c.add(d.get());\n\
+              let out[0] = c.get(); let out[1] = d.get(); return; } }";
+        let counter = "class Counter { field int n;\n\
+            constructor Counter new(int start) { let n = start; return this; }\n\
+            method void add(int x) { let n = n + x; return; }\n\
+            method int get() { return n; } }";
+
+        assert_eq!(vec![36, 21], run(&[main, counter])[..2].to_vec());
+    }
+
+    /// Every program in `projects/11` compiles.
+    #[test]
+    fn test_compile_sources() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../11");
+
+        for dir in fs::read_dir(&root).unwrap() {
+            for entry in fs::read_dir(dir.unwrap().path()).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().is_some_and(|ext| ext == "jack") {
+                    let tokens = tokenize(&fs::read_to_string(&path).unwrap(), 0).unwrap();
+                    if let Err(err) = compile(&parse(&tokens).unwrap()) {
+                        panic!("{}:{}", path.display(), err);
+                    }
+                }
+            }
+        }
+    }
+}
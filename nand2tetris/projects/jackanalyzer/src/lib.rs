@@ -1,2 +1,10 @@
+pub mod ast;
+pub mod build;
+pub mod check;
+pub mod codegen;
+pub mod os;
+pub mod parser;
+pub mod symbols;
 pub mod syntax;
 pub mod token;
+pub mod xml;
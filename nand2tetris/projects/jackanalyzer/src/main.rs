@@ -1,3 +1,104 @@
-fn main() {
-    println!("Hello, world!");
+use jackanalyzer::build::{self, invalid_data, Source};
+use jackanalyzer::check::Strictness;
+use jackanalyzer::os;
+use jackanalyzer::xml;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Args {
+    /// A `.jack` file or a directory of them.
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+
+    /// Compare against the `XxxT.xml` and `Xxx.xml` files already beside each
+    /// source, ignoring whitespace, instead of writing them.
+    #[structopt(long)]
+    compare: bool,
+
+    /// Compile each class to `Xxx.vm` instead of writing XML.
+    #[structopt(long)]
+    vm: bool,
+
+    /// When compiling, keep `boolean`, `int` and each class apart instead of
+    /// treating them all as 16-bit words.
+    #[structopt(long)]
+    strict: bool,
+}
+
+/// The token and parse tree XML of `Xxx.jack`, keyed by their file names.
+fn analyze(source: &Source) -> std::io::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut token_xml = vec![];
+    xml::write_tokens(&source.tokens, &mut token_xml)?;
+    let mut tree_xml = vec![];
+    xml::write_class(&source.class, &mut tree_xml)?;
+
+    let path = &source.path;
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| {
+            build::invalid_data(format!("cannot name the output after {}", path.display()))
+        })?;
+    Ok(vec![
+        (path.with_file_name(format!("{}T.xml", stem)), token_xml),
+        (path.with_extension("xml"), tree_xml),
+    ])
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::from_args();
+
+    let sources = build::files(&args.input, "jack")?
+        .iter()
+        .map(|path| Source::load(path))
+        .collect::<std::io::Result<Vec<Source>>>()?;
+
+    let mut classes = os::classes();
+    for source in &sources {
+        classes.declare(&source.class);
+    }
+    let strictness = if args.strict {
+        Strictness::Strict
+    } else {
+        Strictness::Permissive
+    };
+
+    let mut mismatches = 0;
+
+    for source in &sources {
+        let outputs = if args.vm {
+            let text = build::compile(source, &classes, strictness)?;
+            vec![(source.path.with_extension("vm"), text.into_bytes())]
+        } else {
+            analyze(source)?
+        };
+
+        for (output_path, contents) in outputs {
+            if !args.compare {
+                let mut writer = BufWriter::new(File::create(&output_path)?);
+                writer.write_all(&contents)?;
+                writer.flush()?;
+                continue;
+            }
+
+            let expected = fs::read_to_string(&output_path)?;
+            let actual = String::from_utf8(contents).unwrap();
+            match xml::compare(&expected, &actual) {
+                Ok(()) => println!("{}: ok", output_path.display()),
+                Err(diff) => {
+                    println!("{}: {}", output_path.display(), diff);
+                    mismatches += 1;
+                }
+            }
+        }
+    }
+
+    if mismatches > 0 {
+        return Err(invalid_data(format!("{} file(s) differ", mismatches)));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,139 @@
+use crate::check::Classes;
+use crate::parser::parse;
+use crate::token::tokenize;
+
+/// The Jack OS API, as declared in `09/Jack OS API.pdf`, with empty bodies.
+/// The `init` functions aren't part of the API, but `Sys.init` calls them.
+const API: [&str; 8] = [
+    "class Math {
+        function void init() {}
+        function int abs(int x) {}
+        function int multiply(int x, int y) {}
+        function int divide(int x, int y) {}
+        function int min(int x, int y) {}
+        function int max(int x, int y) {}
+        function int sqrt(int x) {}
+    }",
+    "class String {
+        constructor String new(int maxLength) {}
+        method void dispose() {}
+        method int length() {}
+        method char charAt(int j) {}
+        method void setCharAt(int j, char c) {}
+        method String appendChar(char c) {}
+        method void eraseLastChar() {}
+        method int intValue() {}
+        method void setInt(int j) {}
+        function char backSpace() {}
+        function char doubleQuote() {}
+        function char newLine() {}
+    }",
+    "class Array {
+        function Array new(int size) {}
+        method void dispose() {}
+    }",
+    "class Output {
+        function void init() {}
+        function void moveCursor(int i, int j) {}
+        function void printChar(char c) {}
+        function void printString(String s) {}
+        function void printInt(int i) {}
+        function void println() {}
+        function void backSpace() {}
+    }",
+    "class Screen {
+        function void init() {}
+        function void clearScreen() {}
+        function void setColor(boolean b) {}
+        function void drawPixel(int x, int y) {}
+        function void drawLine(int x1, int y1, int x2, int y2) {}
+        function void drawRectangle(int x1, int y1, int x2, int y2) {}
+        function void drawCircle(int x, int y, int r) {}
+    }",
+    "class Keyboard {
+        function void init() {}
+        function char keyPressed() {}
+        function char readChar() {}
+        function String readLine(String message) {}
+        function int readInt(String message) {}
+    }",
+    "class Memory {
+        function void init() {}
+        function int peek(int address) {}
+        function void poke(int address, int value) {}
+        function Array alloc(int size) {}
+        function void deAlloc(Array o) {}
+    }",
+    "class Sys {
+        function void init() {}
+        function void halt() {}
+        function void error(int errorCode) {}
+        function void wait(int duration) {}
+    }",
+];
+
+/// The OS classes, for checking programs that will be linked against the
+/// OS rather than compiled with its sources. Declaring an OS class from
+/// source afterwards replaces its built-in signatures.
+pub fn classes() -> Classes {
+    let mut classes = Classes::new();
+    for class in API {
+        classes.declare(&parse(&tokenize(class, 0).unwrap()).unwrap());
+    }
+
+    classes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Class, SubroutineKind, Type};
+    use crate::check::Signature;
+    use std::fs;
+    use std::path::Path;
+
+    /// Each API subroutine is in the OS sources of project 12, with the
+    /// same parameters.
+    #[test]
+    fn test_matches_sources() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../12");
+
+        for api in API {
+            let api = parse(&tokenize(api, 0).unwrap()).unwrap();
+            let path = root.join(format!("{}.jack", api.name.node));
+            let tokens = tokenize(&fs::read_to_string(&path).unwrap(), 0).unwrap();
+            let source: Class = parse(&tokens).unwrap();
+
+            for sub in &api.subroutines {
+                let found = source
+                    .subroutines
+                    .iter()
+                    .find(|x| x.name.node == sub.name.node)
+                    .map(Signature::from);
+                let expected = Signature::from(sub);
+                assert_eq!(
+                    Some((expected.kind, expected.params)),
+                    found.map(|x| (x.kind, x.params)),
+                    "{}.{}",
+                    api.name.node,
+                    sub.name.node
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_classes() {
+        let classes = classes();
+        assert!(classes.contains("Keyboard"));
+        assert_eq!(
+            Some(&Signature {
+                kind: SubroutineKind::Function,
+                return_type: None,
+                params: vec![Type::Class("String".to_string())],
+            }),
+            classes.signature("Output", "printString")
+        );
+        assert_eq!(None, classes.signature("Output", "printStr"));
+    }
+}
@@ -0,0 +1,864 @@
+use crate::ast::*;
+use crate::syntax::{KeywordType, Span, Spanned, Symbol, Token};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+fn binary_op(symbol: &Symbol) -> Option<BinaryOp> {
+    match symbol {
+        Symbol::Plus => Some(BinaryOp::Add),
+        Symbol::Minus => Some(BinaryOp::Sub),
+        Symbol::Asterick => Some(BinaryOp::Mul),
+        Symbol::ForwardSlash => Some(BinaryOp::Div),
+        Symbol::Ampersand => Some(BinaryOp::And),
+        Symbol::Pipe => Some(BinaryOp::Or),
+        Symbol::LAngleBracket => Some(BinaryOp::Lt),
+        Symbol::RAngleBracket => Some(BinaryOp::Gt),
+        Symbol::Equal => Some(BinaryOp::Eq),
+        _ => None,
+    }
+}
+
+fn starts_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Keyword(
+            KeywordType::Let
+                | KeywordType::If
+                | KeywordType::While
+                | KeywordType::Do
+                | KeywordType::Return
+                | KeywordType::Var
+        )
+    )
+}
+
+fn starts_class_member(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Keyword(
+            KeywordType::Static
+                | KeywordType::Field
+                | KeywordType::Constructor
+                | KeywordType::Function
+                | KeywordType::Method
+        )
+    )
+}
+
+/// A recursive-descent parser over the tokens of one class.
+///
+/// A malformed statement or class member is recorded and skipped so that
+/// parsing carries on with the next one, and all of the errors come out
+/// together at the end.
+pub struct Parser<'a> {
+    tokens: &'a [Spanned<Token>],
+    pos: usize,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Spanned<Token>]) -> Self {
+        Parser {
+            tokens,
+            pos: 0,
+            errors: vec![],
+        }
+    }
+
+    /// The errors recovered from so far.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<&'a Token> {
+        self.tokens.get(self.pos + ahead).map(|token| &token.node)
+    }
+
+    /// The span of the next token, or an empty one just past the last.
+    fn here(&self) -> Span {
+        match (self.tokens.get(self.pos), self.tokens.last()) {
+            (Some(token), _) => token.span,
+            (None, Some(last)) => Span {
+                start: last.span.end,
+                column: last.span.column + (last.span.end - last.span.start),
+                ..last.span
+            },
+            (None, None) => Span {
+                file: 0,
+                start: 0,
+                end: 0,
+                line: 1,
+                column: 1,
+            },
+        }
+    }
+
+    /// Extends `start` to the end of the last consumed token.
+    fn since(&self, start: Span) -> Span {
+        match self.pos.checked_sub(1).and_then(|idx| self.tokens.get(idx)) {
+            Some(token) => Span {
+                end: token.span.end,
+                ..start
+            },
+            None => start,
+        }
+    }
+
+    fn error<T>(&self, expected: &str) -> ParseResult<T> {
+        let found = match self.peek() {
+            Some(token) => format!("`{}`", token),
+            None => "end of input".to_string(),
+        };
+
+        Err(ParseError {
+            span: self.here(),
+            msg: format!("expected {}, found {}", expected, found),
+        })
+    }
+
+    /// Skips the rest of a malformed statement or declaration that began at
+    /// token `start`: through the next `;`, or up to a `}` or to a token for
+    /// which `starts_next` holds, stepping over any `{ }` blocks on the way.
+    fn synchronize(&mut self, start: usize, starts_next: fn(&Token) -> bool) {
+        let mut depth = 0;
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Symbol(Symbol::LCurlyBrace) => depth += 1,
+                Token::Symbol(Symbol::RCurlyBrace) if depth == 0 => return,
+                Token::Symbol(Symbol::RCurlyBrace) => depth -= 1,
+                Token::Symbol(Symbol::Semicolon) if depth == 0 => {
+                    self.pos += 1;
+                    return;
+                }
+                // Stopping where the error was found would loop forever.
+                token if depth == 0 && self.pos > start && starts_next(token) => return,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Records `err` unless it repeats the last one, which happens when a
+    /// missing `}` at the end of the input is noticed by each enclosing block.
+    fn record(&mut self, err: ParseError) {
+        if self.errors.last().map(|last| last.span) != Some(err.span) {
+            self.errors.push(err);
+        }
+    }
+
+    fn bump(&mut self) -> Option<&'a Spanned<Token>> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn at_symbol(&self, symbol: Symbol) -> bool {
+        self.peek() == Some(&Token::Symbol(symbol))
+    }
+
+    fn at_keyword(&self, keyword: KeywordType) -> bool {
+        self.peek() == Some(&Token::Keyword(keyword))
+    }
+
+    fn eat_symbol(&mut self, symbol: Symbol) -> bool {
+        let found = self.at_symbol(symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: Symbol) -> ParseResult<Span> {
+        if self.at_symbol(symbol.clone()) {
+            Ok(self.bump().unwrap().span)
+        } else {
+            self.error(&format!("`{}`", symbol))
+        }
+    }
+
+    /// Like `expect_symbol`, naming what the symbol should have followed.
+    fn expect_symbol_after(&mut self, symbol: Symbol, after: &str) -> ParseResult<Span> {
+        if self.at_symbol(symbol.clone()) {
+            Ok(self.bump().unwrap().span)
+        } else {
+            self.error(&format!("`{}` after {}", symbol, after))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: KeywordType) -> ParseResult<Span> {
+        if self.at_keyword(keyword.clone()) {
+            Ok(self.bump().unwrap().span)
+        } else {
+            self.error(&format!("`{}`", keyword))
+        }
+    }
+
+    fn ident(&mut self) -> ParseResult<Ident> {
+        match self.tokens.get(self.pos) {
+            Some(Spanned {
+                node: Token::Identifier(name),
+                span,
+            }) => {
+                self.pos += 1;
+                Ok(Spanned {
+                    node: name.to_string(),
+                    span: *span,
+                })
+            }
+            _ => self.error("an identifier"),
+        }
+    }
+
+    fn ty(&mut self) -> ParseResult<Spanned<Type>> {
+        let span = self.here();
+        let ty = match self.peek() {
+            Some(Token::Keyword(KeywordType::Int)) => Type::Int,
+            Some(Token::Keyword(KeywordType::Char)) => Type::Char,
+            Some(Token::Keyword(KeywordType::Boolean)) => Type::Boolean,
+            Some(Token::Identifier(name)) => Type::Class(name.to_string()),
+            _ => return self.error("a type"),
+        };
+        self.pos += 1;
+
+        Ok(Spanned { node: ty, span })
+    }
+
+    /// One or more comma-separated names ending in `;`.
+    fn names(&mut self) -> ParseResult<Vec<Ident>> {
+        let mut names = vec![self.ident()?];
+        while self.eat_symbol(Symbol::Comma) {
+            names.push(self.ident()?);
+        }
+        self.expect_symbol_after(Symbol::Semicolon, "variable declaration")?;
+
+        Ok(names)
+    }
+
+    pub fn class(&mut self) -> ParseResult<Class> {
+        let start = self.expect_keyword(KeywordType::Class)?;
+        let name = self.ident()?;
+        self.expect_symbol_after(Symbol::LCurlyBrace, "class name")?;
+
+        let mut vars = vec![];
+        let mut subroutines = vec![];
+        while !self.at_symbol(Symbol::RCurlyBrace) && self.peek().is_some() {
+            let member_start = self.pos;
+            let member = match self.peek() {
+                Some(Token::Keyword(KeywordType::Static | KeywordType::Field))
+                    if subroutines.is_empty() =>
+                {
+                    self.class_var_dec().map(|var| vars.push(var))
+                }
+                Some(Token::Keyword(
+                    KeywordType::Constructor | KeywordType::Function | KeywordType::Method,
+                )) => self.subroutine_dec().map(|sub| subroutines.push(sub)),
+                _ if subroutines.is_empty() => {
+                    self.error("a class variable or subroutine declaration")
+                }
+                _ => self.error("a subroutine declaration"),
+            };
+
+            if let Err(err) = member {
+                self.record(err);
+                self.synchronize(member_start, starts_class_member);
+            }
+        }
+
+        self.expect_symbol(Symbol::RCurlyBrace)?;
+
+        Ok(Class {
+            name,
+            vars,
+            subroutines,
+            span: self.since(start),
+        })
+    }
+
+    fn class_var_dec(&mut self) -> ParseResult<ClassVarDec> {
+        let start = self.here();
+        let kind = match self.bump().map(|token| &token.node) {
+            Some(Token::Keyword(KeywordType::Static)) => ClassVarKind::Static,
+            _ => ClassVarKind::Field,
+        };
+        let ty = self.ty()?;
+        let names = self.names()?;
+
+        Ok(ClassVarDec {
+            kind,
+            ty,
+            names,
+            span: self.since(start),
+        })
+    }
+
+    fn subroutine_dec(&mut self) -> ParseResult<SubroutineDec> {
+        let start = self.here();
+        let kind = match self.bump().map(|token| &token.node) {
+            Some(Token::Keyword(KeywordType::Constructor)) => SubroutineKind::Constructor,
+            Some(Token::Keyword(KeywordType::Function)) => SubroutineKind::Function,
+            _ => SubroutineKind::Method,
+        };
+
+        let return_type = if self.at_keyword(KeywordType::Void) {
+            self.pos += 1;
+            None
+        } else {
+            Some(self.ty()?)
+        };
+        let name = self.ident()?;
+
+        self.expect_symbol(Symbol::LParen)?;
+        let mut params = vec![];
+        if !self.at_symbol(Symbol::RParen) {
+            loop {
+                let param_start = self.here();
+                let ty = self.ty()?;
+                let name = self.ident()?;
+                params.push(Parameter {
+                    ty,
+                    name,
+                    span: self.since(param_start),
+                });
+
+                if !self.eat_symbol(Symbol::Comma) {
+                    break;
+                }
+            }
+        }
+        self.expect_symbol_after(Symbol::RParen, "parameter list")?;
+
+        let body = self.subroutine_body()?;
+
+        Ok(SubroutineDec {
+            kind,
+            return_type,
+            name,
+            params,
+            body,
+            span: self.since(start),
+        })
+    }
+
+    fn subroutine_body(&mut self) -> ParseResult<SubroutineBody> {
+        let start = self.expect_symbol(Symbol::LCurlyBrace)?;
+
+        let mut vars = vec![];
+        while self.at_keyword(KeywordType::Var) {
+            let var_start = self.pos;
+            match self.var_dec() {
+                Ok(var) => vars.push(var),
+                Err(err) => {
+                    self.record(err);
+                    self.synchronize(var_start, starts_statement);
+                }
+            }
+        }
+
+        let statements = self.statements();
+        self.expect_symbol(Symbol::RCurlyBrace)?;
+
+        Ok(SubroutineBody {
+            vars,
+            statements,
+            span: self.since(start),
+        })
+    }
+
+    fn var_dec(&mut self) -> ParseResult<VarDec> {
+        let start = self.expect_keyword(KeywordType::Var)?;
+        let ty = self.ty()?;
+        let names = self.names()?;
+
+        Ok(VarDec {
+            ty,
+            names,
+            span: self.since(start),
+        })
+    }
+
+    /// Statements up to, but not including, the closing `}`.
+    fn statements(&mut self) -> Vec<Spanned<Statement>> {
+        let mut statements = vec![];
+        while !self.at_symbol(Symbol::RCurlyBrace) && self.peek().is_some() {
+            let start = self.pos;
+            match self.statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    self.record(err);
+                    self.synchronize(start, starts_statement);
+                }
+            }
+        }
+
+        statements
+    }
+
+    /// `{ statements }`
+    fn block(&mut self) -> ParseResult<Vec<Spanned<Statement>>> {
+        self.expect_symbol(Symbol::LCurlyBrace)?;
+        let statements = self.statements();
+        self.expect_symbol(Symbol::RCurlyBrace)?;
+
+        Ok(statements)
+    }
+
+    fn paren_expression(&mut self) -> ParseResult<Expression> {
+        self.expect_symbol(Symbol::LParen)?;
+        let expr = self.expression()?;
+        self.expect_symbol_after(Symbol::RParen, "expression")?;
+
+        Ok(expr)
+    }
+
+    fn statement(&mut self) -> ParseResult<Spanned<Statement>> {
+        let start = self.here();
+
+        let statement = match self.peek() {
+            Some(Token::Keyword(KeywordType::Let)) => {
+                self.pos += 1;
+                let target = self.ident()?;
+                let index = if self.eat_symbol(Symbol::LBracket) {
+                    let index = self.expression()?;
+                    self.expect_symbol_after(Symbol::RBracket, "array index")?;
+                    Some(index)
+                } else {
+                    None
+                };
+                self.expect_symbol(Symbol::Equal)?;
+                let value = self.expression()?;
+                self.expect_symbol_after(Symbol::Semicolon, "`let` statement")?;
+
+                Statement::Let {
+                    target,
+                    index,
+                    value,
+                }
+            }
+            Some(Token::Keyword(KeywordType::If)) => {
+                self.pos += 1;
+                let cond = self.paren_expression()?;
+                let then_branch = self.block()?;
+                let else_branch = if self.at_keyword(KeywordType::Else) {
+                    self.pos += 1;
+                    Some(self.block()?)
+                } else {
+                    None
+                };
+
+                Statement::If {
+                    cond,
+                    then_branch,
+                    else_branch,
+                }
+            }
+            Some(Token::Keyword(KeywordType::While)) => {
+                self.pos += 1;
+                let cond = self.paren_expression()?;
+                let body = self.block()?;
+
+                Statement::While { cond, body }
+            }
+            Some(Token::Keyword(KeywordType::Do)) => {
+                self.pos += 1;
+                let name = self.ident()?;
+                let call = self.subroutine_call(name)?;
+                self.expect_symbol_after(Symbol::Semicolon, "`do` statement")?;
+
+                Statement::Do(call)
+            }
+            Some(Token::Keyword(KeywordType::Return)) => {
+                self.pos += 1;
+                let value = if self.at_symbol(Symbol::Semicolon) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect_symbol_after(Symbol::Semicolon, "`return` statement")?;
+
+                Statement::Return(value)
+            }
+            _ => return self.error("a statement"),
+        };
+
+        Ok(Spanned {
+            node: statement,
+            span: self.since(start),
+        })
+    }
+
+    /// The rest of a call after its first name, which is either the
+    /// subroutine or, before a `.`, its receiver.
+    fn subroutine_call(&mut self, first: Ident) -> ParseResult<SubroutineCall> {
+        let start = first.span;
+        let (receiver, name) = if self.eat_symbol(Symbol::Period) {
+            (Some(first), self.ident()?)
+        } else {
+            (None, first)
+        };
+
+        self.expect_symbol(Symbol::LParen)?;
+        let mut args = vec![];
+        if !self.at_symbol(Symbol::RParen) {
+            args.push(self.expression()?);
+            while self.eat_symbol(Symbol::Comma) {
+                args.push(self.expression()?);
+            }
+        }
+        self.expect_symbol_after(Symbol::RParen, "expression list")?;
+
+        Ok(SubroutineCall {
+            receiver,
+            name,
+            args,
+            span: self.since(start),
+        })
+    }
+
+    pub fn expression(&mut self) -> ParseResult<Expression> {
+        let start = self.here();
+        let first = Box::new(self.term()?);
+
+        let mut rest = vec![];
+        while let Some(op) = match self.peek() {
+            Some(Token::Symbol(symbol)) => binary_op(symbol),
+            _ => None,
+        } {
+            let op = Spanned {
+                node: op,
+                span: self.bump().unwrap().span,
+            };
+            rest.push((op, self.term()?));
+        }
+
+        Ok(Expression {
+            first,
+            rest,
+            span: self.since(start),
+        })
+    }
+
+    fn term(&mut self) -> ParseResult<Spanned<Term>> {
+        let start = self.here();
+
+        let term = match self.peek() {
+            Some(Token::IntConst(val)) => {
+                self.pos += 1;
+                Term::IntConst(*val)
+            }
+            Some(Token::StringConst(text)) => {
+                self.pos += 1;
+                Term::StringConst(text.to_string())
+            }
+            Some(Token::Keyword(keyword)) => {
+                let keyword = match keyword {
+                    KeywordType::True => KeywordConst::True,
+                    KeywordType::False => KeywordConst::False,
+                    KeywordType::Null => KeywordConst::Null,
+                    KeywordType::This => KeywordConst::This,
+                    _ => return self.error("a term"),
+                };
+                self.pos += 1;
+                Term::Keyword(keyword)
+            }
+            Some(Token::Symbol(Symbol::LParen)) => Term::Paren(Box::new(self.paren_expression()?)),
+            Some(Token::Symbol(symbol @ (Symbol::Minus | Symbol::Tilde))) => {
+                let op = match symbol {
+                    Symbol::Minus => UnaryOp::Neg,
+                    _ => UnaryOp::Not,
+                };
+                let op = Spanned {
+                    node: op,
+                    span: self.bump().unwrap().span,
+                };
+                Term::Unary(op, Box::new(self.term()?))
+            }
+            Some(Token::Identifier(_)) => match self.peek_at(1) {
+                Some(Token::Symbol(Symbol::LBracket)) => {
+                    let name = self.ident()?;
+                    self.pos += 1;
+                    let index = self.expression()?;
+                    self.expect_symbol_after(Symbol::RBracket, "array index")?;
+                    Term::Index(name, Box::new(index))
+                }
+                Some(Token::Symbol(Symbol::LParen | Symbol::Period)) => {
+                    let name = self.ident()?;
+                    Term::Call(self.subroutine_call(name)?)
+                }
+                _ => Term::Var(self.ident()?.node),
+            },
+            _ => return self.error("a term"),
+        };
+
+        Ok(Spanned {
+            node: term,
+            span: self.since(start),
+        })
+    }
+
+    /// Fails unless every token has been consumed.
+    pub fn finish(&self) -> ParseResult<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => self.error("end of input"),
+        }
+    }
+}
+
+/// Parses the tokens of a `.jack` file, which hold exactly one class,
+/// returning every error found if there are any.
+pub fn parse(tokens: &[Spanned<Token>]) -> Result<Class, Vec<ParseError>> {
+    let mut parser = Parser::new(tokens);
+    let class = parser.class().and_then(|class| {
+        parser.finish()?;
+        Ok(class)
+    });
+
+    match class {
+        Ok(class) if parser.errors.is_empty() => Ok(class),
+        Ok(_) => Err(parser.errors),
+        Err(err) => {
+            parser.record(err);
+            Err(parser.errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::tokenize;
+    use std::fs;
+    use std::path::Path;
+
+    fn parse_str(input: &str) -> Result<Class, Vec<ParseError>> {
+        parse(&tokenize(input, 0).unwrap())
+    }
+
+    fn errors(input: &str) -> Vec<String> {
+        parse_str(input)
+            .unwrap_err()
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    fn expression(input: &str) -> Expression {
+        let tokens = tokenize(input, 0).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let expr = parser.expression().unwrap();
+        parser.finish().unwrap();
+
+        expr
+    }
+
+    #[test]
+    fn test_class() {
+        let input = "class Main {\n  static int a, b;\n  field Array c;\n\
+                     method void f(int x, char y) { var boolean z; return; }\n}";
+        let class = parse_str(input).unwrap();
+
+        assert_eq!("Main", class.name.node);
+        assert_eq!(0..input.len(), class.span.start..class.span.end);
+        assert_eq!(2, class.vars.len());
+        assert_eq!(ClassVarKind::Static, class.vars[0].kind);
+        assert_eq!(
+            vec!["a", "b"],
+            class.vars[0]
+                .names
+                .iter()
+                .map(|x| x.node.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(Type::Class("Array".to_string()), class.vars[1].ty.node);
+
+        let method = &class.subroutines[0];
+        assert_eq!(SubroutineKind::Method, method.kind);
+        assert_eq!(None, method.return_type);
+        assert_eq!(2, method.params.len());
+        assert_eq!(Type::Char, method.params[1].ty.node);
+        assert_eq!(Type::Boolean, method.body.vars[0].ty.node);
+        assert_eq!(Statement::Return(None), method.body.statements[0].node);
+        assert_eq!(
+            "method void f(int x, char y) { var boolean z; return; }",
+            &input[method.span.start..method.span.end]
+        );
+    }
+
+    #[test]
+    fn test_expression_terms() {
+        let expr = expression("a[i] + Foo.bar(1, \"s\") * -(x) & ~this");
+        let ops = expr
+            .rest
+            .iter()
+            .map(|(op, _)| op.node)
+            .collect::<Vec<BinaryOp>>();
+
+        assert_eq!(vec![BinaryOp::Add, BinaryOp::Mul, BinaryOp::And], ops);
+        assert!(matches!(expr.first.node, Term::Index(ref name, _) if name.node == "a"));
+        match &expr.rest[0].1.node {
+            Term::Call(call) => {
+                assert_eq!(Some("Foo"), call.receiver.as_ref().map(|x| x.node.as_str()));
+                assert_eq!("bar", call.name.node);
+                assert_eq!(2, call.args.len());
+            }
+            term => panic!("expected a call, found {:?}", term),
+        }
+        assert!(matches!(
+            expr.rest[1].1.node,
+            Term::Unary(
+                Spanned {
+                    node: UnaryOp::Neg,
+                    ..
+                },
+                _
+            )
+        ));
+        assert!(matches!(
+            &expr.rest[2].1.node,
+            Term::Unary(_, term) if term.node == Term::Keyword(KeywordConst::This)
+        ));
+    }
+
+    #[test]
+    fn test_statements() {
+        let input = "class A { function void f() {\n\
+                     let x[1] = 2; if (x) { do g(); } else { while (y) { } } return x;\n\
+                     } }";
+        let class = parse_str(input).unwrap();
+        let statements = &class.subroutines[0].body.statements;
+
+        assert!(matches!(
+            statements[0].node,
+            Statement::Let { index: Some(_), .. }
+        ));
+        assert!(matches!(
+            &statements[1].node,
+            Statement::If { else_branch: Some(branch), .. } if branch.len() == 1
+        ));
+        assert!(matches!(statements[2].node, Statement::Return(Some(_))));
+        assert_eq!((2, 1), (statements[0].span.line, statements[0].span.column));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            vec!["3:1: expected `;` after variable declaration, found `}`"],
+            errors("class Main {\n  field int x\n}")
+        );
+        assert_eq!(
+            vec!["1:38: expected an identifier, found `=`"],
+            errors("class Main { function void f() { let = 1; } }")
+        );
+        assert_eq!(
+            vec!["1:13: expected `}`, found end of input"],
+            errors("class Main {")
+        );
+        assert_eq!(
+            vec!["1:16: expected end of input, found `class`"],
+            errors("class Main { } class")
+        );
+        assert_eq!(
+            vec!["1:40: expected `)` after expression list, found `;`"],
+            errors("class Main { function void f() { do g(1; return; } }")
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        let input = "class Main {\n\
+                     field int x y;\n\
+                     field int z;\n\
+                     method void f( { let x = 1; }\n\
+                     function void g() {\n\
+                     var int a\n\
+                     let a = ;\n\
+                     if (a { do h(); }\n\
+                     while (a) { let = 2; do h(); }\n\
+                     return;\n\
+                     }\n\
+                     field int w;\n\
+                     }";
+        assert_eq!(
+            vec![
+                "2:13: expected `;` after variable declaration, found `y`",
+                "4:16: expected a type, found `{`",
+                "7:1: expected `;` after variable declaration, found `let`",
+                "7:9: expected a term, found `;`",
+                "8:7: expected `)` after expression, found `{`",
+                "9:17: expected an identifier, found `=`",
+                "12:1: expected a subroutine declaration, found `field`",
+            ],
+            errors(input)
+        );
+
+        let tokens = tokenize(input, 0).unwrap();
+        let mut parser = Parser::new(&tokens);
+        let class = parser.class().unwrap();
+        assert_eq!(7, parser.errors().len());
+        assert_eq!(
+            vec!["z"],
+            class
+                .vars
+                .iter()
+                .map(|var| var.names[0].node.as_str())
+                .collect::<Vec<&str>>()
+        );
+        let body = &class.subroutines[0].body;
+        assert!(body.vars.is_empty());
+        assert_eq!(2, body.statements.len());
+        assert!(matches!(
+            &body.statements[0].node,
+            Statement::While { body, .. } if body.len() == 1
+        ));
+    }
+
+    /// Every Jack source in the repository parses.
+    #[test]
+    fn test_parse_sources() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let mut dirs = vec![
+            root.join("09"),
+            root.join("10"),
+            root.join("11"),
+            root.join("12"),
+        ];
+        let mut count = 0;
+
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "jack") {
+                    let tokens = tokenize(&fs::read_to_string(&path).unwrap(), 0).unwrap();
+                    if let Err(errors) = parse(&tokens) {
+                        panic!("{}:{}", path.display(), errors[0]);
+                    }
+                    count += 1;
+                }
+            }
+        }
+
+        assert!(count > 0);
+    }
+}
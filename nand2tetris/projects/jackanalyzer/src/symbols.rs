@@ -0,0 +1,107 @@
+use crate::ast::Type;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Static,
+    Field,
+    Argument,
+    Var,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variable {
+    pub ty: Type,
+    pub kind: Kind,
+    /// The variable's offset within its kind's segment.
+    pub index: usize,
+}
+
+/// Variables visible in a subroutine: the class's statics and fields, then
+/// the subroutine's own arguments and locals, which shadow them.
+#[derive(Default)]
+pub struct SymbolTable {
+    class: HashMap<String, Variable>,
+    subroutine: HashMap<String, Variable>,
+    counts: HashMap<Kind, usize>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    /// Forgets the previous subroutine's arguments and locals.
+    pub fn start_subroutine(&mut self) {
+        self.subroutine.clear();
+        self.counts.remove(&Kind::Argument);
+        self.counts.remove(&Kind::Var);
+    }
+
+    /// Adds a variable at the next index of its kind, returning `false` if
+    /// the name is already defined in the same scope.
+    pub fn define(&mut self, name: &str, ty: Type, kind: Kind) -> bool {
+        let count = self.counts.entry(kind).or_insert(0);
+        let scope = match kind {
+            Kind::Static | Kind::Field => &mut self.class,
+            Kind::Argument | Kind::Var => &mut self.subroutine,
+        };
+
+        if scope.contains_key(name) {
+            return false;
+        }
+
+        scope.insert(
+            name.to_string(),
+            Variable {
+                ty,
+                kind,
+                index: *count,
+            },
+        );
+        *count += 1;
+        true
+    }
+
+    pub fn var_count(&self, kind: Kind) -> usize {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.subroutine.get(name).or_else(|| self.class.get(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        let mut table = SymbolTable::new();
+        assert!(table.define("x", Type::Int, Kind::Field));
+        assert!(table.define("y", Type::Int, Kind::Field));
+        assert!(table.define("count", Type::Int, Kind::Static));
+        assert!(!table.define("x", Type::Char, Kind::Static));
+
+        table.start_subroutine();
+        assert!(table.define("this", Type::Class("Point".to_string()), Kind::Argument));
+        assert!(table.define("x", Type::Boolean, Kind::Var));
+
+        assert_eq!(
+            Some(&Variable {
+                ty: Type::Boolean,
+                kind: Kind::Var,
+                index: 0
+            }),
+            table.lookup("x")
+        );
+        assert_eq!(1, table.lookup("y").unwrap().index);
+        assert_eq!(2, table.var_count(Kind::Field));
+
+        table.start_subroutine();
+        assert_eq!(Kind::Field, table.lookup("x").unwrap().kind);
+        assert_eq!(0, table.var_count(Kind::Argument));
+        assert_eq!(1, table.var_count(Kind::Static));
+    }
+}
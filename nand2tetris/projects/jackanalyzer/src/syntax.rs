@@ -1,5 +1,4 @@
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -58,6 +57,36 @@ impl FromStr for KeywordType {
     }
 }
 
+impl fmt::Display for KeywordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self {
+            Self::Class => "class",
+            Self::Method => "method",
+            Self::Function => "function",
+            Self::Constructor => "constructor",
+            Self::Int => "int",
+            Self::Boolean => "boolean",
+            Self::Char => "char",
+            Self::Void => "void",
+            Self::Var => "var",
+            Self::Static => "static",
+            Self::Field => "field",
+            Self::Let => "let",
+            Self::Do => "do",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::While => "while",
+            Self::Return => "return",
+            Self::True => "true",
+            Self::False => "false",
+            Self::Null => "null",
+            Self::This => "this",
+        };
+
+        write!(f, "{}", keyword)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Symbol {
     LCurlyBrace,
@@ -110,6 +139,34 @@ impl FromStr for Symbol {
     }
 }
 
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::LCurlyBrace => "{",
+            Self::RCurlyBrace => "}",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::Period => ".",
+            Self::Comma => ",",
+            Self::Semicolon => ";",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Asterick => "*",
+            Self::ForwardSlash => "/",
+            Self::Ampersand => "&",
+            Self::Pipe => "|",
+            Self::LAngleBracket => "<",
+            Self::RAngleBracket => ">",
+            Self::Equal => "=",
+            Self::Tilde => "~",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Keyword(KeywordType),
@@ -119,41 +176,86 @@ pub enum Token {
     StringConst(String),
 }
 
-impl FromStr for Token {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref STRING_CONST_RE: Regex = Regex::new(r#"^"[^"]*"$"#).unwrap();
-            static ref IDENTIFIER_RE: Regex = Regex::new("^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keyword(keyword) => write!(f, "{}", keyword),
+            Self::Symbol(symbol) => write!(f, "{}", symbol),
+            Self::Identifier(name) => write!(f, "{}", name),
+            Self::IntConst(val) => write!(f, "{}", val),
+            Self::StringConst(text) => write!(f, "\"{}\"", text),
         }
+    }
+}
 
-        if let Ok(keyword) = s.parse::<KeywordType>() {
-            return Ok(Self::Keyword(keyword));
-        }
-        if let Ok(symbol) = s.parse::<Symbol>() {
-            return Ok(Self::Symbol(symbol));
-        }
-        if let Ok(num) = s.parse::<i16>() {
-            return Ok(Self::IntConst(num));
-        }
-        if STRING_CONST_RE.is_match(s) {
-            return Ok(Self::StringConst(s.replace("\"", "")));
-        }
-        if IDENTIFIER_RE.is_match(s) {
-            return Ok(Self::Identifier(s.to_string()));
-        }
+/// Identifies a source file among those being compiled together.
+pub type FileId = usize;
 
-        Err(s.to_string())
+/// A stretch of source text: its byte range within the file, and the line
+/// and column (both from 1) where it starts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
     }
 }
 
+impl Span {
+    /// The line of `src` this span starts on, underlined up to the span's
+    /// end or the end of the line:
+    ///
+    /// ```text
+    ///   |
+    /// 3 |   field int x
+    ///   |              ^
+    /// ```
+    pub fn snippet(&self, src: &str) -> String {
+        let text = src.lines().nth(self.line - 1).unwrap_or("");
+        // Keep tabs so the caret lines up however the terminal expands them.
+        let indent = text
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let rest = text.chars().count().saturating_sub(self.column - 1);
+        let width = src
+            .get(self.start..self.end)
+            .map_or(0, |slice| slice.chars().count())
+            .min(rest)
+            .max(1);
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        format!(
+            "{} |\n{} | {}\n{} | {}{}",
+            gutter,
+            self.line,
+            text,
+            gutter,
+            indent,
+            "^".repeat(width)
+        )
+    }
+}
+
+/// A value along with the source it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
 #[cfg(test)]
 mod tests {
     use super::KeywordType::*;
     use super::Symbol::*;
-    use super::Token;
-    use super::Token::*;
     use std::fmt::Debug;
     use std::str::FromStr;
 
@@ -174,35 +276,20 @@ mod tests {
     }
 
     #[test]
-    fn parse_token_keyword() {
-        parse_tester("class", Keyword(Class));
-    }
-
-    #[test]
-    fn parse_token_symbol() {
-        parse_tester("{", Symbol(LCurlyBrace));
-    }
-
-    #[test]
-    fn parse_token_int_const() {
-        parse_tester("42", IntConst(42));
-    }
-
-    #[test]
-    fn parse_token_string_const() {
-        parse_tester("\"my_const\"", StringConst("my_const".to_string()));
-        assert_eq!(Err("\"hello\"}".to_string()), "\"hello\"}".parse::<Token>());
-        assert_eq!(
-            Err("\"hello\"world\"".to_string()),
-            "\"hello\"world\"".parse::<Token>()
-        );
+    fn parse_symbol() {
+        parse_tester("{", LCurlyBrace);
     }
 
     #[test]
-    fn parse_token_identifier() {
-        parse_tester("_hello", Identifier("_hello".to_string()));
-        parse_tester("_hello0", Identifier("_hello0".to_string()));
-        assert_eq!(Err("0hello".to_string()), "0hello".parse::<Token>());
-        assert_eq!(Err("_hello;".to_string()), "_hello;".parse::<Token>());
+    fn span_snippet() {
+        let src = "class Main {\r\n\tfield int x\r\n}";
+        let span = super::Span {
+            file: 0,
+            start: 21,
+            end: 24,
+            line: 2,
+            column: 8,
+        };
+        assert_eq!("  |\n2 | \tfield int x\n  | \t      ^^^", span.snippet(src));
     }
 }
@@ -1,163 +1,234 @@
-use crate::syntax::Token;
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::collections::VecDeque;
-use std::io::{BufRead, Lines};
-
-fn is_comment(line: &str) -> bool {
-    line.starts_with("//")
+use crate::syntax::{FileId, KeywordType, Span, Spanned, Symbol, Token};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub span: Span,
+    pub msg: String,
 }
 
-/// Determines if a line has no effect on the program.
-fn superficial(line: &str) -> bool {
-    line.is_empty() || is_comment(line)
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.msg)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Scans Jack source one character at a time.
+pub struct Tokenizer<'a> {
+    chars: Peekable<Chars<'a>>,
+    file: FileId,
+    offset: usize,
+    line: usize,
+    column: usize,
 }
 
-fn strip_trailing_comment(line: &str) -> String {
-    match line.find("//") {
-        Some(offset) => {
-            let mut new_line = line.to_string();
-            new_line.replace_range(offset.., "");
-            new_line.trim().to_string()
+impl<'a> Tokenizer<'a> {
+    pub fn new(src: &'a str, file: FileId) -> Self {
+        Tokenizer {
+            chars: src.chars().peekable(),
+            file,
+            offset: 0,
+            line: 1,
+            column: 1,
         }
-        None => line.to_string(),
     }
-}
 
-fn clean_line(line: &str) -> String {
-    let trimmed = line.trim();
-    strip_trailing_comment(trimmed)
-}
+    /// An empty span at the current position.
+    fn here(&self) -> Span {
+        Span {
+            file: self.file,
+            start: self.offset,
+            end: self.offset,
+            line: self.line,
+            column: self.column,
+        }
+    }
 
-fn grab_next_line<T: BufRead>(lines: &mut Lines<T>) -> Option<String> {
-    match lines.next() {
-        Some(res) => match res {
-            Ok(line) => {
-                if line.is_empty() {
-                    grab_next_line(lines)
-                } else {
-                    Some(line)
-                }
+    /// Extends `start` up to the current position.
+    fn since(&self, start: Span) -> Span {
+        Span {
+            end: self.offset,
+            ..start
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn bump_while<F: Fn(char) -> bool>(&mut self, pred: F) -> String {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !pred(c) {
+                break;
             }
-            Err(_) => None,
-        },
-        None => None,
+            text.push(c);
+            self.bump();
+        }
+
+        text
     }
-}
 
-fn char_slice_to_token(chars: &[char]) -> Result<Token, String> {
-    chars.iter().collect::<String>().parse()
-}
+    /// Skips whitespace and comments, failing on an unterminated block comment.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            self.bump_while(char::is_whitespace);
 
-fn nested_tokenize(nested_string: &str) -> Vec<Token> {
-    let mut tokens = vec![];
-    let chars = nested_string.chars().collect::<Vec<char>>();
-
-    if !chars.is_empty() {
-        let mut next_slice_idx = 0;
-        let mut next_token = None;
-
-        for i in 0..chars.len() {
-            next_token = match char_slice_to_token(&chars[0..i + 1]) {
-                Ok(token) => Some(token),
-                Err(_) => {
-                    if next_token.is_some() {
-                        break;
+            if self.chars.peek() != Some(&'/') {
+                return Ok(());
+            }
+
+            let mut ahead = self.chars.clone();
+            ahead.next();
+            match ahead.peek() {
+                Some('/') => {
+                    self.bump_while(|c| c != '\n');
+                }
+                Some('*') => {
+                    let start = self.here();
+                    self.bump();
+                    self.bump();
+
+                    let mut prev = None;
+                    loop {
+                        match self.bump() {
+                            Some('/') if prev == Some('*') => break,
+                            Some(c) => prev = Some(c),
+                            None => {
+                                return Err(LexError {
+                                    span: self.since(start),
+                                    msg: "unterminated comment".to_string(),
+                                })
+                            }
+                        }
                     }
-                    None
                 }
-            };
-
-            next_slice_idx = i;
+                _ => return Ok(()),
+            }
         }
+    }
 
-        if let Some(token) = next_token {
-            tokens.push(token);
-            tokens.append(&mut nested_tokenize(
-                &chars[next_slice_idx + 1..].iter().collect::<String>(),
-            ));
+    fn string_const(&mut self) -> Result<Token, String> {
+        self.bump();
+        let text = self.bump_while(|c| c != '"' && c != '\n');
+
+        match self.bump() {
+            Some('"') => Ok(Token::StringConst(text)),
+            _ => Err("unterminated string constant".to_string()),
         }
     }
 
-    tokens
-}
+    fn int_const(&mut self) -> Result<Token, String> {
+        let digits = self.bump_while(|c| c.is_ascii_digit());
 
-fn take_until_valid_line<T: BufRead>(lines: &mut Lines<T>) -> Option<String> {
-    let mut cleaned_line = None;
+        digits
+            .parse::<i16>()
+            .map(Token::IntConst)
+            .map_err(|_| format!("integer constant {} is larger than 32767", digits))
+    }
 
-    while cleaned_line.is_none() {
-        let cur_line = grab_next_line(lines)?;
-        let clean_line = clean_line(&cur_line);
+    fn word(&mut self) -> Token {
+        let word = self.bump_while(|c| c.is_ascii_alphanumeric() || c == '_');
 
-        if !clean_line.is_empty() {
-            cleaned_line = Some(clean_line);
+        match word.parse::<KeywordType>() {
+            Ok(keyword) => Token::Keyword(keyword),
+            Err(_) => Token::Identifier(word),
         }
     }
 
-    cleaned_line
-}
-
-fn tokenize<T: BufRead>(lines: &mut Lines<T>) -> Option<Vec<Token>> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r#"('.*?'|".*?"|\S+)"#).unwrap();
-    }
+    /// The next token, or `None` at the end of input. After an error,
+    /// scanning resumes past the offending text.
+    pub fn advance(&mut self) -> Option<Result<Spanned<Token>, LexError>> {
+        if let Err(err) = self.skip_trivia() {
+            return Some(Err(err));
+        }
 
-    let cur_line = take_until_valid_line(lines)?;
+        let start = self.here();
+        let c = *self.chars.peek()?;
+
+        let token = match c {
+            '"' => self.string_const(),
+            '0'..='9' => self.int_const(),
+            'a'..='z' | 'A'..='Z' | '_' => Ok(self.word()),
+            _ => {
+                self.bump();
+                c.to_string()
+                    .parse::<Symbol>()
+                    .map(Token::Symbol)
+                    .map_err(|_| format!("illegal character `{}`", c))
+            }
+        };
 
-    let tokens = RE
-        .find_iter(&cur_line)
-        .map(|x| x.as_str())
-        .map(|x| x.parse::<Token>())
-        .map(|x| match x {
-            Ok(x) => vec![x],
-            Err(error_string) => nested_tokenize(&error_string),
+        let span = self.since(start);
+        Some(match token {
+            Ok(node) => Ok(Spanned { node, span }),
+            Err(msg) => Err(LexError { span, msg }),
         })
-        .flatten()
-        .collect::<Vec<Token>>();
-
-    Some(tokens)
+    }
 }
 
-struct Tokenizer<T: BufRead> {
-    lines: Lines<T>,
-    cur_tokens: VecDeque<Token>,
-}
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Spanned<Token>, LexError>;
 
-impl<T: BufRead> Tokenizer<T> {
-    fn new(lines: Lines<T>) -> Self {
-        Tokenizer {
-            lines,
-            cur_tokens: VecDeque::new(),
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        self.advance()
     }
+}
+
+/// Scans all of `src`, reporting every error rather than stopping at the first.
+pub fn tokenize(src: &str, file: FileId) -> Result<Vec<Spanned<Token>>, Vec<LexError>> {
+    let mut tokens = vec![];
+    let mut errors = vec![];
 
-    fn advance(&mut self) -> Option<Token> {
-        if self.cur_tokens.is_empty() {
-            self.cur_tokens = VecDeque::from(tokenize(&mut self.lines)?);
+    for result in Tokenizer::new(src, file) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(err) => errors.push(err),
         }
+    }
 
-        self.cur_tokens.pop_front()
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::syntax::{KeywordType, Symbol};
-    use std::io::{BufRead, BufReader};
 
     fn test_iter(input: &str, expected: &[Token]) {
-        let reader = BufReader::new(input.as_bytes());
-        let mut tokenizer = Tokenizer::new(reader.lines());
-
-        let mut actual = vec![];
-        while let Some(token) = tokenizer.advance() {
-            actual.push(token)
-        }
+        let actual = tokenize(input, 0)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.node)
+            .collect::<Vec<Token>>();
 
         assert_eq!(expected, actual);
     }
 
+    fn errors(input: &str) -> Vec<String> {
+        tokenize(input, 0)
+            .unwrap_err()
+            .iter()
+            .map(|x| x.to_string())
+            .collect()
+    }
+
     /// While invalid to neglect a semi-colon, it's easier to parse.
     /// This allows building simple token-based parsing without a look ahead.
     #[test]
@@ -266,6 +337,31 @@ mod tests {
         test_iter(input, &expected);
     }
 
+    #[test]
+    fn test_block_comments() {
+        let input = r#"
+        /** Spans
+         *  lines. */
+        let x /* inline */ = 1; /* trailing
+        */ let y = 2;
+        "#;
+
+        let expected = [
+            Token::Keyword(KeywordType::Let),
+            Token::Identifier("x".to_string()),
+            Token::Symbol("=".parse().unwrap()),
+            Token::IntConst(1),
+            Token::Symbol(";".parse().unwrap()),
+            Token::Keyword(KeywordType::Let),
+            Token::Identifier("y".to_string()),
+            Token::Symbol("=".parse().unwrap()),
+            Token::IntConst(2),
+            Token::Symbol(";".parse().unwrap()),
+        ];
+
+        test_iter(input, &expected);
+    }
+
     /// Test that string constants won't be split.
     /// No semi-colon as that complicates the parsing.
     #[test]
@@ -282,17 +378,15 @@ mod tests {
     }
 
     #[test]
-    fn test_nested_tokenize_num_semi() {
+    fn test_adjacent_num_semi() {
         let input = "42;";
         let expected = vec![Token::IntConst(42), Token::Symbol(Symbol::Semicolon)];
 
-        let actual = nested_tokenize(input);
-
-        assert_eq!(expected, actual);
+        test_iter(input, &expected);
     }
 
     #[test]
-    fn test_nested_tokenize_curly_brace_str_const() {
+    fn test_adjacent_curly_brace_str_const() {
         let input = "{\"hello\"}";
         let expected = vec![
             Token::Symbol(Symbol::LCurlyBrace),
@@ -300,13 +394,11 @@ mod tests {
             Token::Symbol(Symbol::RCurlyBrace),
         ];
 
-        let actual = nested_tokenize(input);
-
-        assert_eq!(expected, actual);
+        test_iter(input, &expected);
     }
 
     #[test]
-    fn test_nested_tokenize_curly_brace_str_const_twice() {
+    fn test_adjacent_curly_brace_str_const_twice() {
         let input = "{\"hello\"}{\"hello\"}";
         let expected = vec![
             Token::Symbol(Symbol::LCurlyBrace),
@@ -317,8 +409,56 @@ mod tests {
             Token::Symbol(Symbol::RCurlyBrace),
         ];
 
-        let actual = nested_tokenize(input);
+        test_iter(input, &expected);
+    }
 
-        assert_eq!(expected, actual);
+    #[test]
+    fn test_comment_markers_in_strings() {
+        let input = "let s = \"http://a /* b */\"; // \"not a string\"";
+        let expected = [
+            Token::Keyword(KeywordType::Let),
+            Token::Identifier("s".to_string()),
+            Token::Symbol(Symbol::Equal),
+            Token::StringConst("http://a /* b */".to_string()),
+            Token::Symbol(Symbol::Semicolon),
+        ];
+
+        test_iter(input, &expected);
+    }
+
+    #[test]
+    fn test_spans() {
+        let input = "/** doc\n */\nclass Main {\n  x-1 \"é\"";
+        let spans = tokenize(input, 3)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.span)
+            .collect::<Vec<Span>>();
+
+        let lines_cols = spans
+            .iter()
+            .map(|span| (span.line, span.column))
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(
+            vec![(3, 1), (3, 7), (3, 12), (4, 3), (4, 4), (4, 5), (4, 7)],
+            lines_cols
+        );
+
+        let last = spans.last().unwrap();
+        assert_eq!(3, last.file);
+        assert_eq!("\"é\"", &input[last.start..last.end]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            vec![
+                "1:5: illegal character `#`",
+                "2:9: unterminated string constant",
+                "3:1: integer constant 40000 is larger than 32767",
+                "3:7: unterminated comment",
+            ],
+            errors("let #x;\nlet s = \"oops\n40000 /* never closed")
+        );
     }
 }
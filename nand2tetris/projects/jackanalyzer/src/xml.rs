@@ -0,0 +1,564 @@
+use crate::ast::*;
+use crate::syntax::{KeywordType, Spanned, Symbol, Token};
+use std::io::{self, Write};
+
+/// The course's fixtures end lines the Windows way.
+const NEWLINE: &str = "\r\n";
+
+/// Escapes the characters XML reserves.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The element a token is written as, e.g. `<keyword> class </keyword>`.
+pub fn token_element(token: &Token) -> String {
+    let (tag, text) = match token {
+        Token::Keyword(keyword) => ("keyword", keyword.to_string()),
+        Token::Symbol(symbol) => ("symbol", symbol.to_string()),
+        Token::Identifier(name) => ("identifier", name.to_string()),
+        Token::IntConst(val) => ("integerConstant", val.to_string()),
+        Token::StringConst(text) => ("stringConstant", text.to_string()),
+    };
+
+    format!("<{}> {} </{}>", tag, escape(&text), tag)
+}
+
+/// Writes tokens in the format of the course's `XxxT.xml` files.
+pub fn write_tokens<W: Write>(tokens: &[Spanned<Token>], out: &mut W) -> io::Result<()> {
+    write!(out, "<tokens>{}", NEWLINE)?;
+    for token in tokens {
+        write!(out, "{}{}", token_element(&token.node), NEWLINE)?;
+    }
+    write!(out, "</tokens>{}", NEWLINE)
+}
+
+fn binary_symbol(op: BinaryOp) -> Symbol {
+    match op {
+        BinaryOp::Add => Symbol::Plus,
+        BinaryOp::Sub => Symbol::Minus,
+        BinaryOp::Mul => Symbol::Asterick,
+        BinaryOp::Div => Symbol::ForwardSlash,
+        BinaryOp::And => Symbol::Ampersand,
+        BinaryOp::Or => Symbol::Pipe,
+        BinaryOp::Lt => Symbol::LAngleBracket,
+        BinaryOp::Gt => Symbol::RAngleBracket,
+        BinaryOp::Eq => Symbol::Equal,
+    }
+}
+
+/// Writes the parse tree of the course's `Xxx.xml` files, indenting each
+/// level by two spaces.
+struct TreeWriter<'a, W: Write> {
+    out: &'a mut W,
+    depth: usize,
+}
+
+impl<'a, W: Write> TreeWriter<'a, W> {
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        write!(
+            self.out,
+            "{:indent$}{}{}",
+            "",
+            text,
+            NEWLINE,
+            indent = self.depth * 2
+        )
+    }
+
+    fn open(&mut self, tag: &str) -> io::Result<()> {
+        self.line(&format!("<{}>", tag))?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn close(&mut self, tag: &str) -> io::Result<()> {
+        self.depth -= 1;
+        self.line(&format!("</{}>", tag))
+    }
+
+    fn token(&mut self, token: Token) -> io::Result<()> {
+        self.line(&token_element(&token))
+    }
+
+    fn keyword(&mut self, keyword: KeywordType) -> io::Result<()> {
+        self.token(Token::Keyword(keyword))
+    }
+
+    fn symbol(&mut self, symbol: Symbol) -> io::Result<()> {
+        self.token(Token::Symbol(symbol))
+    }
+
+    fn ident(&mut self, name: &str) -> io::Result<()> {
+        self.token(Token::Identifier(name.to_string()))
+    }
+
+    fn ty(&mut self, ty: &Type) -> io::Result<()> {
+        match ty {
+            Type::Int => self.keyword(KeywordType::Int),
+            Type::Char => self.keyword(KeywordType::Char),
+            Type::Boolean => self.keyword(KeywordType::Boolean),
+            Type::Class(name) => self.ident(name),
+        }
+    }
+
+    /// `a, b, c;`
+    fn names(&mut self, names: &[Ident]) -> io::Result<()> {
+        for (idx, name) in names.iter().enumerate() {
+            if idx > 0 {
+                self.symbol(Symbol::Comma)?;
+            }
+            self.ident(&name.node)?;
+        }
+        self.symbol(Symbol::Semicolon)
+    }
+
+    fn class(&mut self, class: &Class) -> io::Result<()> {
+        self.open("class")?;
+        self.keyword(KeywordType::Class)?;
+        self.ident(&class.name.node)?;
+        self.symbol(Symbol::LCurlyBrace)?;
+
+        for var in &class.vars {
+            self.open("classVarDec")?;
+            self.keyword(match var.kind {
+                ClassVarKind::Static => KeywordType::Static,
+                ClassVarKind::Field => KeywordType::Field,
+            })?;
+            self.ty(&var.ty.node)?;
+            self.names(&var.names)?;
+            self.close("classVarDec")?;
+        }
+
+        for subroutine in &class.subroutines {
+            self.subroutine_dec(subroutine)?;
+        }
+
+        self.symbol(Symbol::RCurlyBrace)?;
+        self.close("class")
+    }
+
+    fn subroutine_dec(&mut self, subroutine: &SubroutineDec) -> io::Result<()> {
+        self.open("subroutineDec")?;
+        self.keyword(match subroutine.kind {
+            SubroutineKind::Constructor => KeywordType::Constructor,
+            SubroutineKind::Function => KeywordType::Function,
+            SubroutineKind::Method => KeywordType::Method,
+        })?;
+        match &subroutine.return_type {
+            Some(ty) => self.ty(&ty.node)?,
+            None => self.keyword(KeywordType::Void)?,
+        }
+        self.ident(&subroutine.name.node)?;
+
+        self.symbol(Symbol::LParen)?;
+        self.open("parameterList")?;
+        for (idx, param) in subroutine.params.iter().enumerate() {
+            if idx > 0 {
+                self.symbol(Symbol::Comma)?;
+            }
+            self.ty(&param.ty.node)?;
+            self.ident(&param.name.node)?;
+        }
+        self.close("parameterList")?;
+        self.symbol(Symbol::RParen)?;
+
+        let body = &subroutine.body;
+        self.open("subroutineBody")?;
+        self.symbol(Symbol::LCurlyBrace)?;
+        for var in &body.vars {
+            self.open("varDec")?;
+            self.keyword(KeywordType::Var)?;
+            self.ty(&var.ty.node)?;
+            self.names(&var.names)?;
+            self.close("varDec")?;
+        }
+        self.statements(&body.statements)?;
+        self.symbol(Symbol::RCurlyBrace)?;
+        self.close("subroutineBody")?;
+
+        self.close("subroutineDec")
+    }
+
+    fn statements(&mut self, statements: &[Spanned<Statement>]) -> io::Result<()> {
+        self.open("statements")?;
+        for statement in statements {
+            self.statement(&statement.node)?;
+        }
+        self.close("statements")
+    }
+
+    /// `{ statements }`
+    fn block(&mut self, statements: &[Spanned<Statement>]) -> io::Result<()> {
+        self.symbol(Symbol::LCurlyBrace)?;
+        self.statements(statements)?;
+        self.symbol(Symbol::RCurlyBrace)
+    }
+
+    fn paren_expression(&mut self, expr: &Expression) -> io::Result<()> {
+        self.symbol(Symbol::LParen)?;
+        self.expression(expr)?;
+        self.symbol(Symbol::RParen)
+    }
+
+    fn statement(&mut self, statement: &Statement) -> io::Result<()> {
+        match statement {
+            Statement::Let {
+                target,
+                index,
+                value,
+            } => {
+                self.open("letStatement")?;
+                self.keyword(KeywordType::Let)?;
+                self.ident(&target.node)?;
+                if let Some(index) = index {
+                    self.symbol(Symbol::LBracket)?;
+                    self.expression(index)?;
+                    self.symbol(Symbol::RBracket)?;
+                }
+                self.symbol(Symbol::Equal)?;
+                self.expression(value)?;
+                self.symbol(Symbol::Semicolon)?;
+                self.close("letStatement")
+            }
+            Statement::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.open("ifStatement")?;
+                self.keyword(KeywordType::If)?;
+                self.paren_expression(cond)?;
+                self.block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.keyword(KeywordType::Else)?;
+                    self.block(else_branch)?;
+                }
+                self.close("ifStatement")
+            }
+            Statement::While { cond, body } => {
+                self.open("whileStatement")?;
+                self.keyword(KeywordType::While)?;
+                self.paren_expression(cond)?;
+                self.block(body)?;
+                self.close("whileStatement")
+            }
+            Statement::Do(call) => {
+                self.open("doStatement")?;
+                self.keyword(KeywordType::Do)?;
+                self.subroutine_call(call)?;
+                self.symbol(Symbol::Semicolon)?;
+                self.close("doStatement")
+            }
+            Statement::Return(value) => {
+                self.open("returnStatement")?;
+                self.keyword(KeywordType::Return)?;
+                if let Some(value) = value {
+                    self.expression(value)?;
+                }
+                self.symbol(Symbol::Semicolon)?;
+                self.close("returnStatement")
+            }
+        }
+    }
+
+    /// Calls have no element of their own; their parts sit in the enclosing
+    /// term or `doStatement`.
+    fn subroutine_call(&mut self, call: &SubroutineCall) -> io::Result<()> {
+        if let Some(receiver) = &call.receiver {
+            self.ident(&receiver.node)?;
+            self.symbol(Symbol::Period)?;
+        }
+        self.ident(&call.name.node)?;
+
+        self.symbol(Symbol::LParen)?;
+        self.open("expressionList")?;
+        for (idx, arg) in call.args.iter().enumerate() {
+            if idx > 0 {
+                self.symbol(Symbol::Comma)?;
+            }
+            self.expression(arg)?;
+        }
+        self.close("expressionList")?;
+        self.symbol(Symbol::RParen)
+    }
+
+    fn expression(&mut self, expr: &Expression) -> io::Result<()> {
+        self.open("expression")?;
+        self.term(&expr.first.node)?;
+        for (op, term) in &expr.rest {
+            self.symbol(binary_symbol(op.node))?;
+            self.term(&term.node)?;
+        }
+        self.close("expression")
+    }
+
+    fn term(&mut self, term: &Term) -> io::Result<()> {
+        self.open("term")?;
+        match term {
+            Term::IntConst(val) => self.token(Token::IntConst(*val))?,
+            Term::StringConst(text) => self.token(Token::StringConst(text.to_string()))?,
+            Term::Keyword(keyword) => self.keyword(match keyword {
+                KeywordConst::True => KeywordType::True,
+                KeywordConst::False => KeywordType::False,
+                KeywordConst::Null => KeywordType::Null,
+                KeywordConst::This => KeywordType::This,
+            })?,
+            Term::Var(name) => self.ident(name)?,
+            Term::Index(name, index) => {
+                self.ident(&name.node)?;
+                self.symbol(Symbol::LBracket)?;
+                self.expression(index)?;
+                self.symbol(Symbol::RBracket)?;
+            }
+            Term::Call(call) => self.subroutine_call(call)?,
+            Term::Paren(expr) => self.paren_expression(expr)?,
+            Term::Unary(op, term) => {
+                self.symbol(match op.node {
+                    UnaryOp::Neg => Symbol::Minus,
+                    UnaryOp::Not => Symbol::Tilde,
+                })?;
+                self.term(&term.node)?;
+            }
+        }
+        self.close("term")
+    }
+}
+
+/// Writes a class in the format of the course's `Xxx.xml` parse trees.
+pub fn write_class<W: Write>(class: &Class, out: &mut W) -> io::Result<()> {
+    TreeWriter { out, depth: 0 }.class(class)
+}
+
+enum Element {
+    Open(String),
+    Close(String),
+    Leaf(String),
+}
+
+/// Splits XML into elements, ignoring all whitespace like the course's
+/// TextComparer. A leaf is an element whose content is text.
+fn elements(xml: &str) -> Vec<Element> {
+    let text = xml
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let mut rest = text.as_str();
+    let mut elements = vec![];
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let tag_end = rest.find('>').map_or(rest.len(), |idx| idx + 1);
+        let tag = &rest[1..tag_end.saturating_sub(1).max(1)];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            elements.push(Element::Close(name.to_string()));
+            rest = &rest[tag_end..];
+        } else if rest[tag_end..].starts_with('<') || tag_end == rest.len() {
+            elements.push(Element::Open(tag.to_string()));
+            rest = &rest[tag_end..];
+        } else {
+            let close = format!("</{}>", tag);
+            let end = rest[tag_end..]
+                .find(&close)
+                .map_or(rest.len(), |idx| tag_end + idx + close.len());
+            elements.push(Element::Leaf(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+
+    elements
+}
+
+impl Element {
+    fn text(&self) -> String {
+        match self {
+            Element::Open(tag) => format!("<{}>", tag),
+            Element::Close(tag) => format!("</{}>", tag),
+            Element::Leaf(text) => text.to_string(),
+        }
+    }
+}
+
+/// Compares two XML documents ignoring whitespace, describing the first
+/// difference by the path of elements leading to it, like
+/// `class/subroutineDec[2]/subroutineBody/statements/letStatement[1]`.
+pub fn compare(expected: &str, actual: &str) -> Result<(), String> {
+    let expected = elements(expected);
+    let actual = elements(actual);
+
+    // Each open element with how many of its children had each tag.
+    let mut path: Vec<(String, Vec<(String, usize)>)> = vec![];
+    let mut roots: Vec<(String, usize)> = vec![];
+
+    for idx in 0..expected.len().max(actual.len()) {
+        let (exp, act) = match (expected.get(idx), actual.get(idx)) {
+            (Some(exp), Some(act)) => (exp.text(), act.text()),
+            (Some(exp), None) => (exp.text(), "end of document".to_string()),
+            (None, Some(act)) => ("end of document".to_string(), act.text()),
+            (None, None) => unreachable!(),
+        };
+
+        if exp != act {
+            let location = path
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<&str>>()
+                .join("/");
+            return Err(format!(
+                "at {}: expected {}, found {}",
+                if location.is_empty() { "/" } else { &location },
+                exp,
+                act
+            ));
+        }
+
+        match &expected[idx] {
+            Element::Open(tag) => {
+                let siblings = match path.last_mut() {
+                    Some((_, children)) => children,
+                    None => &mut roots,
+                };
+                let count = match siblings.iter_mut().find(|(name, _)| name == tag) {
+                    Some((_, count)) => {
+                        *count += 1;
+                        *count
+                    }
+                    None => {
+                        siblings.push((tag.to_string(), 1));
+                        1
+                    }
+                };
+                let name = if path.is_empty() {
+                    tag.to_string()
+                } else {
+                    format!("{}[{}]", tag, count)
+                };
+                path.push((name, vec![]));
+            }
+            Element::Close(_) => {
+                path.pop();
+            }
+            Element::Leaf(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::token::tokenize;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn fixtures(suffix: &str) -> Vec<PathBuf> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../10");
+        let mut fixtures = vec![];
+
+        for dir in fs::read_dir(&root).unwrap() {
+            for file in fs::read_dir(dir.unwrap().path()).unwrap() {
+                let path = file.unwrap().path();
+                let name = path.file_name().unwrap().to_str().unwrap();
+                if name.ends_with(suffix) {
+                    fixtures.push(path);
+                }
+            }
+        }
+        assert!(!fixtures.is_empty());
+
+        fixtures
+    }
+
+    fn tree_xml(path: &Path) -> String {
+        let tokens = tokenize(&fs::read_to_string(path).unwrap(), 0).unwrap();
+        let mut out = vec![];
+        write_class(&parse(&tokens).unwrap(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn tokens_xml(path: &Path) -> String {
+        let tokens = tokenize(&fs::read_to_string(path).unwrap(), 0).unwrap();
+
+        let mut out = vec![];
+        write_tokens(&tokens, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_token_element() {
+        assert_eq!(
+            "<symbol> &lt; </symbol>",
+            token_element(&Token::Symbol("<".parse().unwrap()))
+        );
+        assert_eq!(
+            "<stringConstant> a &amp; b </stringConstant>",
+            token_element(&Token::StringConst("a & b".to_string()))
+        );
+    }
+
+    /// Matches every `XxxT.xml` in `projects/10` byte for byte.
+    #[test]
+    fn test_token_fixtures() {
+        for expected_path in fixtures("T.xml") {
+            let stem = expected_path.file_stem().unwrap().to_str().unwrap();
+            let source = expected_path.with_file_name(format!("{}.jack", &stem[..stem.len() - 1]));
+
+            assert_eq!(
+                fs::read_to_string(&expected_path).unwrap(),
+                tokens_xml(&source),
+                "{}",
+                source.display()
+            );
+        }
+    }
+
+    /// Matches every parse tree `Xxx.xml` in `projects/10` byte for byte.
+    #[test]
+    fn test_tree_fixtures() {
+        for expected_path in fixtures(".xml") {
+            if expected_path.to_str().unwrap().ends_with("T.xml") {
+                continue;
+            }
+            let source = expected_path.with_extension("jack");
+
+            assert_eq!(
+                fs::read_to_string(&expected_path).unwrap(),
+                tree_xml(&source),
+                "{}",
+                source.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let expected =
+            "<class>\n  <keyword> class </keyword>\n  <subroutineDec>\n  </subroutineDec>\n\
+                        <subroutineDec>\n    <symbol> ( </symbol>\n  </subroutineDec>\n</class>\n";
+
+        assert_eq!(
+            Ok(()),
+            compare(expected, &expected.replace("\n", "\r\n    "))
+        );
+        assert_eq!(
+            Err(
+                "at class/subroutineDec[2]: expected <symbol>(</symbol>, found <symbol>)</symbol>"
+                    .to_string()
+            ),
+            compare(expected, &expected.replace('(', ")"))
+        );
+        assert_eq!(
+            Err("at class: expected </class>, found end of document".to_string()),
+            compare(
+                expected,
+                "<class><keyword>class</keyword><subroutineDec></subroutineDec>\
+                               <subroutineDec><symbol>(</symbol></subroutineDec>"
+            )
+        );
+    }
+}
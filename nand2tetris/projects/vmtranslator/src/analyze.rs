@@ -0,0 +1,421 @@
+use crate::callgraph::CallGraph;
+use crate::command::{ArithmeticOp, Command};
+use crate::error::VmError;
+use crate::ir::{Located, Program};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+pub const HEAP_BASE: usize = 2048;
+
+/// Words pushed by `call` on top of the arguments.
+pub const FRAME_SIZE: usize = 5;
+
+#[derive(Debug, PartialEq)]
+pub struct FunctionReport {
+    pub name: String,
+    pub locals: usize,
+    /// Largest stack use within the function's own frame, locals included.
+    pub max_depth: usize,
+    /// Largest stack use including callees, or `None` when unbounded.
+    pub worst_case: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub functions: Vec<FunctionReport>,
+    pub recursion: Vec<Vec<String>>,
+    pub warnings: Vec<VmError>,
+    pub entry: Option<String>,
+    /// Highest address the stack can reach from the entry function.
+    pub peak: Option<usize>,
+}
+
+struct Frame {
+    max_depth: usize,
+    calls: BTreeMap<String, usize>,
+}
+
+fn stack_effect(cmd: &Command) -> i64 {
+    match cmd {
+        Command::Push(_) => 1,
+        Command::Pop(_) | Command::IfGoto(_) => -1,
+        Command::Arithmetic(ArithmeticOp::Negate) | Command::Arithmetic(ArithmeticOp::Not) => 0,
+        Command::Arithmetic(_) => -1,
+        Command::Call(_, arg_cnt) | Command::TailCall(_, arg_cnt) => 1 - *arg_cnt as i64,
+        _ => 0,
+    }
+}
+
+/// Follows every path through a function body, tracking the stack depth
+/// relative to `LCL`.
+fn analyze_frame(
+    name: &str,
+    locals: usize,
+    body: &[Located],
+    warnings: &mut Vec<VmError>,
+) -> Frame {
+    let labels = body
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, located)| match &located.cmd {
+            Command::Label(lbl) => Some((lbl.as_str(), idx)),
+            _ => None,
+        })
+        .collect::<HashMap<&str, usize>>();
+
+    let base = locals as i64;
+    let mut depths: Vec<Option<i64>> = vec![None; body.len()];
+    let mut pending = vec![];
+    let mut frame = Frame {
+        max_depth: locals,
+        calls: BTreeMap::new(),
+    };
+    let mut underflowed = false;
+
+    if !body.is_empty() {
+        depths[0] = Some(base);
+        pending.push(0);
+    }
+
+    while let Some(idx) = pending.pop() {
+        let located = &body[idx];
+        let depth = depths[idx].unwrap();
+
+        if let Command::Call(func, _) | Command::TailCall(func, _) = &located.cmd {
+            let at_call = frame.calls.entry(func.to_string()).or_insert(0);
+            *at_call = (*at_call).max(depth as usize);
+        }
+
+        let after = depth + stack_effect(&located.cmd);
+        frame.max_depth = frame.max_depth.max(after.max(0) as usize);
+
+        if after < base && !underflowed {
+            underflowed = true;
+            warnings.push(VmError::at(
+                &located.pos,
+                &format!("{} pops below its frame base", name),
+            ));
+        }
+
+        let successors = match &located.cmd {
+            Command::Goto(lbl) => vec![labels.get(lbl.as_str()).copied()],
+            Command::IfGoto(lbl) => vec![Some(idx + 1), labels.get(lbl.as_str()).copied()],
+            Command::Return => {
+                if after != base + 1 {
+                    warnings.push(VmError::at(
+                        &located.pos,
+                        &format!(
+                            "{} returns with {} values on its stack, expected 1",
+                            name,
+                            after - base
+                        ),
+                    ));
+                }
+                vec![]
+            }
+            // The callee returns to our caller.
+            Command::TailCall(..) => vec![],
+            _ => vec![Some(idx + 1)],
+        };
+
+        for next in successors.into_iter().flatten() {
+            if next >= body.len() {
+                continue;
+            }
+
+            match depths[next] {
+                None => {
+                    depths[next] = Some(after);
+                    pending.push(next);
+                }
+                Some(prev) if prev != after => warnings.push(VmError::at(
+                    &body[next].pos,
+                    &format!(
+                        "{} reaches this command with stack depths {} and {}",
+                        name,
+                        prev - base,
+                        after - base
+                    ),
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    frame
+}
+
+/// Groups functions that can call themselves, directly or indirectly.
+fn recursive_groups(graph: &CallGraph) -> Vec<Vec<String>> {
+    let reach = graph
+        .definitions
+        .keys()
+        .map(|func| {
+            let roots = graph
+                .callees(func)
+                .map(|x| x.as_str())
+                .collect::<Vec<&str>>();
+            (func.as_str(), graph.reachable_from(&roots))
+        })
+        .collect::<BTreeMap<&str, BTreeSet<String>>>();
+
+    let mut groups: Vec<Vec<String>> = vec![];
+
+    for (func, reachable) in &reach {
+        if !reachable.contains(*func) || groups.iter().any(|g| g.iter().any(|x| x == func)) {
+            continue;
+        }
+
+        let group = reachable
+            .iter()
+            .filter(|other| {
+                reach
+                    .get(other.as_str())
+                    .is_some_and(|back| back.contains(*func))
+            })
+            .cloned()
+            .collect();
+        groups.push(group);
+    }
+
+    groups
+}
+
+fn worst_case(
+    func: &str,
+    frames: &BTreeMap<String, Frame>,
+    recursive: &BTreeSet<String>,
+    memo: &mut HashMap<String, Option<usize>>,
+) -> Option<usize> {
+    if recursive.contains(func) {
+        return None;
+    }
+    if let Some(usage) = memo.get(func) {
+        return *usage;
+    }
+
+    // Calls into functions outside the program are assumed to use no stack.
+    let usage = match frames.get(func) {
+        None => Some(0),
+        Some(frame) => frame
+            .calls
+            .iter()
+            .try_fold(frame.max_depth, |usage, (callee, depth)| {
+                let callee_usage = worst_case(callee, frames, recursive, memo)?;
+                Some(usage.max(depth + FRAME_SIZE + callee_usage))
+            }),
+    };
+
+    memo.insert(func.to_string(), usage);
+    usage
+}
+
+/// Analyzes `program` as if the bootstrap calls `entry` with `SP` at `stack_base`.
+pub fn analyze(program: &Program, entry: &str, stack_base: usize) -> Report {
+    let graph = CallGraph::build(program);
+    let mut report = Report::default();
+    let mut frames = BTreeMap::new();
+    let mut locals = BTreeMap::new();
+
+    for module in &program.modules {
+        let starts = module
+            .commands
+            .iter()
+            .enumerate()
+            .filter(|(_, located)| matches!(located.cmd, Command::Function(..)))
+            .map(|(idx, _)| idx)
+            .chain(std::iter::once(module.commands.len()))
+            .collect::<Vec<usize>>();
+
+        for bounds in starts.windows(2) {
+            if let Command::Function(name, local_cnt) = &module.commands[bounds[0]].cmd {
+                let body = &module.commands[bounds[0] + 1..bounds[1]];
+                let frame = analyze_frame(name, *local_cnt, body, &mut report.warnings);
+                frames.insert(name.to_string(), frame);
+                locals.insert(name.to_string(), *local_cnt);
+            }
+        }
+    }
+
+    report.recursion = recursive_groups(&graph);
+    let recursive = report
+        .recursion
+        .iter()
+        .flatten()
+        .cloned()
+        .collect::<BTreeSet<String>>();
+
+    let mut memo = HashMap::new();
+    for (name, frame) in &frames {
+        report.functions.push(FunctionReport {
+            name: name.to_string(),
+            locals: locals[name],
+            max_depth: frame.max_depth,
+            worst_case: worst_case(name, &frames, &recursive, &mut memo),
+        });
+    }
+
+    if frames.contains_key(entry) {
+        report.entry = Some(entry.to_string());
+        report.peak = worst_case(entry, &frames, &recursive, &mut memo)
+            .map(|usage| stack_base + FRAME_SIZE + usage);
+
+        match report.peak {
+            Some(peak) if peak > HEAP_BASE => report.warnings.push(VmError::new(&format!(
+                "stack can grow to RAM[{}], past the heap base at {}",
+                peak - 1,
+                HEAP_BASE
+            ))),
+            None => report.warnings.push(VmError::new(&format!(
+                "recursion reachable from {} makes the stack depth unbounded",
+                entry
+            ))),
+            _ => {}
+        }
+    }
+
+    report
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|func| func.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+
+        writeln!(
+            f,
+            "{:width$}  {:>6}  {:>9}  {:>10}",
+            "function",
+            "locals",
+            "max depth",
+            "worst case",
+            width = width
+        )?;
+        for func in &self.functions {
+            let worst_case = match func.worst_case {
+                Some(usage) => usage.to_string(),
+                None => "unbounded".to_string(),
+            };
+            writeln!(
+                f,
+                "{:width$}  {:>6}  {:>9}  {:>10}",
+                func.name,
+                func.locals,
+                func.max_depth,
+                worst_case,
+                width = width
+            )?;
+        }
+
+        for group in &self.recursion {
+            writeln!(f, "recursion: {}", group.join(", "))?;
+        }
+
+        if let (Some(entry), Some(peak)) = (&self.entry, self.peak) {
+            writeln!(f, "stack from {} reaches at most RAM[{}]", entry, peak - 1)?;
+        }
+
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn report(input: &str) -> Report {
+        let program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+
+        analyze(&program, "Sys.init", 256)
+    }
+
+    fn func<'a>(report: &'a Report, name: &str) -> &'a FunctionReport {
+        report.functions.iter().find(|x| x.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_worst_case() {
+        let input = "function Sys.init 1\npush constant 1\npush constant 2\ncall Main.add 2\n\
+                     pop local 0\nlabel END\ngoto END\n\
+                     function Main.add 0\npush argument 0\npush argument 1\nadd\nreturn\n";
+        let report = report(input);
+
+        assert_eq!(
+            &FunctionReport {
+                name: "Main.add".to_string(),
+                locals: 0,
+                max_depth: 2,
+                worst_case: Some(2),
+            },
+            func(&report, "Main.add")
+        );
+        // Local and both arguments, a call frame, then the callee's stack.
+        assert_eq!(Some(3 + 5 + 2), func(&report, "Sys.init").worst_case);
+        assert_eq!(Some(256 + 5 + 10), report.peak);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unbalanced_return() {
+        let input = "function Main.f 0\npush constant 1\npush constant 2\nreturn\n";
+        let report = report(input);
+
+        assert_eq!(1, report.warnings.len());
+        assert_eq!(
+            "Main.vm:4: Main.f returns with 2 values on its stack, expected 1",
+            report.warnings[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_branch_depths() {
+        let input = "function Main.f 0\npush constant 1\nif-goto A\npush constant 1\n\
+                     label A\npush constant 1\nreturn\n";
+        let report = report(input);
+
+        assert!(report
+            .warnings
+            .iter()
+            .any(|x| x.to_string().starts_with("Main.vm:5: Main.f reaches")));
+    }
+
+    #[test]
+    fn test_recursion() {
+        let input = "function Sys.init 0\ncall Main.a 0\nreturn\n\
+                     function Main.a 0\ncall Main.b 0\nreturn\n\
+                     function Main.b 0\ncall Main.a 0\nreturn\n";
+        let report = report(input);
+
+        assert_eq!(
+            vec![vec!["Main.a".to_string(), "Main.b".to_string()]],
+            report.recursion
+        );
+        assert_eq!(None, func(&report, "Sys.init").worst_case);
+        assert_eq!(None, report.peak);
+    }
+
+    #[test]
+    fn test_heap_overflow() {
+        let pushes = "push constant 0\n".repeat(2000);
+        let input = format!("function Sys.init 0\n{}label END\ngoto END\n", pushes);
+        let report = report(&input);
+
+        assert!(report
+            .warnings
+            .iter()
+            .any(|x| x.to_string().contains("past the heap base")));
+    }
+}
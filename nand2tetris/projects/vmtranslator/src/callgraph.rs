@@ -0,0 +1,106 @@
+use crate::command::Command;
+use crate::ir::{Position, Program};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Which functions each function calls, keyed by caller.
+///
+/// Calls made outside any function are recorded under `None`.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    pub calls: BTreeMap<Option<String>, BTreeSet<String>>,
+    pub definitions: BTreeMap<String, Position>,
+}
+
+impl CallGraph {
+    pub fn build(program: &Program) -> Self {
+        let mut graph = CallGraph::default();
+
+        for module in &program.modules {
+            let mut cur_func = None;
+            graph.calls.entry(None).or_default();
+
+            for located in &module.commands {
+                match &located.cmd {
+                    Command::Function(func, _) => {
+                        cur_func = Some(func.to_string());
+                        graph
+                            .definitions
+                            .entry(func.to_string())
+                            .or_insert_with(|| located.pos.clone());
+                        graph.calls.entry(cur_func.clone()).or_default();
+                    }
+                    Command::Call(func, _) | Command::TailCall(func, _) => {
+                        graph
+                            .calls
+                            .entry(cur_func.clone())
+                            .or_default()
+                            .insert(func.to_string());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        graph
+    }
+
+    pub fn callees(&self, func: &str) -> impl Iterator<Item = &String> {
+        self.calls
+            .get(&Some(func.to_string()))
+            .into_iter()
+            .flat_map(|callees| callees.iter())
+    }
+
+    /// Every function reachable from `roots` and from code outside functions.
+    pub fn reachable(&self, roots: &[&str]) -> BTreeSet<String> {
+        let mut roots = roots.to_vec();
+        if let Some(callees) = self.calls.get(&None) {
+            roots.extend(callees.iter().map(|x| x.as_str()));
+        }
+
+        self.reachable_from(&roots)
+    }
+
+    /// Every function reachable from `roots`, including the roots.
+    pub fn reachable_from(&self, roots: &[&str]) -> BTreeSet<String> {
+        let mut seen = BTreeSet::new();
+        let mut pending = roots
+            .iter()
+            .map(|root| root.to_string())
+            .collect::<Vec<String>>();
+
+        while let Some(func) = pending.pop() {
+            if seen.insert(func.clone()) {
+                pending.extend(self.callees(&func).cloned());
+            }
+        }
+
+        seen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_reachable() {
+        let input =
+            "function Sys.init 0\ncall Main.main 0\nfunction Main.main 0\ncall Main.main 0\n\
+                     function Main.unused 0\ncall Sys.init 0\n";
+        let program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Sys").unwrap()],
+        };
+        let graph = CallGraph::build(&program);
+
+        let expected = ["Main.main", "Sys.init"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<BTreeSet<String>>();
+
+        assert_eq!(expected, graph.reachable(&["Sys.init"]));
+        assert_eq!(3, graph.definitions.len());
+    }
+}
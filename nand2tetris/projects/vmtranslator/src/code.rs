@@ -1,6 +1,18 @@
 use crate::command::{ArithmeticOp, Command, Segment};
 use crate::instruct::Instruction;
+use crate::ir::Position;
+use crate::sourcemap::{Entry, SourceMap};
+use assembler::assemble;
 use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+/// The number of ROM words the instructions assemble to.
+fn rom_len(instructs: &[Instruction]) -> usize {
+    instructs
+        .iter()
+        .filter(|x| !matches!(x, Instruction::L(_) | Instruction::Comment(_)))
+        .count()
+}
 
 fn instruct_vec_str(instructs: &[Instruction]) -> String {
     instructs
@@ -11,19 +23,27 @@ fn instruct_vec_str(instructs: &[Instruction]) -> String {
 }
 
 fn a_const(val: &i16) -> Instruction {
-    Instruction::AConst(*val)
+    Instruction::AConst(i32::from(*val))
 }
 
 fn a_sym(sym: &str) -> Instruction {
-    Instruction::ASymbolic(sym.to_string())
+    Instruction::AVar(sym.to_string())
 }
 
 fn addr_assign(lhs: &str, rhs: &str) -> Instruction {
-    Instruction::C(Some(lhs.to_string()), rhs.to_string(), None)
+    Instruction::C {
+        dest: Some(lhs.to_string()),
+        comp: rhs.to_string(),
+        jump: None,
+    }
 }
 
 fn jmp(comp: &str, jump: &str) -> Instruction {
-    Instruction::C(None, comp.to_string(), Some(jump.to_string()))
+    Instruction::C {
+        dest: None,
+        comp: comp.to_string(),
+        jump: Some(jump.to_string()),
+    }
 }
 
 fn jmp_no_cond() -> Instruction {
@@ -31,7 +51,11 @@ fn jmp_no_cond() -> Instruction {
 }
 
 fn label(label: &str) -> Instruction {
-    Instruction::Label(label.to_string())
+    Instruction::L(label.to_string())
+}
+
+fn comment(text: &str) -> Instruction {
+    Instruction::Comment(text.to_string())
 }
 
 fn segment_offset_template(base_reg: &str, offset: &i16) -> Vec<Instruction> {
@@ -74,16 +98,25 @@ fn segment_to_hack(segment: &Segment) -> Vec<Instruction> {
     }
 }
 
-fn assembly_header() -> Vec<Instruction> {
+fn assembly_header(bootstrap: &Bootstrap) -> Vec<Instruction> {
     let mut instructs = vec![
-        a_const(&256),
+        a_const(&bootstrap.stack_base),
         addr_assign("D", "A"),
         a_sym("SP"),
         addr_assign("M", "D"),
     ];
 
+    if bootstrap.sentinels {
+        for (reg, val) in ["LCL", "ARG", "THIS", "THAT"].iter().zip(1..) {
+            instructs.push(a_const(&val));
+            instructs.push(addr_assign("D", "-A"));
+            instructs.push(a_sym(reg));
+            instructs.push(addr_assign("M", "D"));
+        }
+    }
+
     instructs.append(&mut emit_call(
-        "Sys.init",
+        &bootstrap.entry,
         &0,
         &0,
         &Some("_internal_vm_translator".to_string()),
@@ -160,29 +193,102 @@ fn comparator_template(jump: &str, line_idx: usize) -> Vec<Instruction> {
     let neg_cond = format!("_neg_cond_{}", line_idx);
 
     instructs.push(addr_assign("D", "M-D"));
-    instructs.push(a_sym(&pos_cond));
-    instructs.push(jmp("D", jump));
-    instructs.push(a_sym(&neg_cond));
-    instructs.push(addr_assign("D", "0"));
+    instructs.append(&mut comparator_result(jump, &pos_cond, &neg_cond));
+
+    instructs
+}
+
+fn comparator_result(jump: &str, pos_cond: &str, neg_cond: &str) -> Vec<Instruction> {
+    let mut instructs = vec![
+        a_sym(pos_cond),
+        jmp("D", jump),
+        a_sym(neg_cond),
+        addr_assign("D", "0"),
+        jmp_no_cond(),
+        label(pos_cond),
+        addr_assign("D", "-1"),
+        label(neg_cond),
+    ];
+
+    instructs.append(&mut stack_top());
+    instructs.push(addr_assign("M", "D"));
+
+    instructs
+}
+
+/// Compares without computing `x - y` when the operands' signs differ, as the
+/// subtraction can overflow (e.g. `32767 gt -2`).
+fn signed_comparator_template(jump: &str, line_idx: usize) -> Vec<Instruction> {
+    let mut instructs = vec![];
+
+    let x_neg = format!("_x_neg_{}", line_idx);
+    let same_sign = format!("_same_sign_{}", line_idx);
+    let cmp = format!("_cmp_{}", line_idx);
+    let pos_cond = format!("_pos_cond_{}", line_idx);
+    let neg_cond = format!("_neg_cond_{}", line_idx);
+
+    let y = a_sym("R13");
+
+    instructs.push(y.clone());
+    instructs.push(addr_assign("M", "D"));
+    instructs.append(&mut stack_top());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym(&x_neg));
+    instructs.push(jmp("D", "JLT"));
+
+    // x >= 0, so x > y when y < 0.
+    instructs.push(y.clone());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym(&same_sign));
+    instructs.push(jmp("D", "JGE"));
+    instructs.push(addr_assign("D", "1"));
+    instructs.push(a_sym(&cmp));
     instructs.push(jmp_no_cond());
-    instructs.push(label(&pos_cond));
+
+    // x < 0, so x < y when y >= 0.
+    instructs.push(label(&x_neg));
+    instructs.push(y.clone());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym(&same_sign));
+    instructs.push(jmp("D", "JLT"));
     instructs.push(addr_assign("D", "-1"));
-    instructs.push(label(&neg_cond));
+    instructs.push(a_sym(&cmp));
+    instructs.push(jmp_no_cond());
+
+    // Operands with the same sign cannot overflow.
+    instructs.push(label(&same_sign));
+    instructs.push(y);
+    instructs.push(addr_assign("D", "M"));
     instructs.append(&mut stack_top());
-    instructs.push(addr_assign("M", "D"));
+    instructs.push(addr_assign("D", "M-D"));
+
+    instructs.push(label(&cmp));
+    instructs.append(&mut comparator_result(jump, &pos_cond, &neg_cond));
 
     instructs
 }
 
-fn arithmetic_two_stack_val(op: &ArithmeticOp, line_idx: usize) -> Vec<Instruction> {
+fn ordering_template(jump: &str, line_idx: usize, options: &Options) -> Vec<Instruction> {
+    if options.fast_compare {
+        comparator_template(jump, line_idx)
+    } else {
+        signed_comparator_template(jump, line_idx)
+    }
+}
+
+fn arithmetic_two_stack_val(
+    op: &ArithmeticOp,
+    line_idx: usize,
+    options: &Options,
+) -> Vec<Instruction> {
     let mut op = match op {
         ArithmeticOp::Add => vec![addr_assign("M", "M+D")],
         ArithmeticOp::Subtract => vec![addr_assign("M", "M-D")],
         ArithmeticOp::And => vec![addr_assign("M", "M&D")],
         ArithmeticOp::Or => vec![addr_assign("M", "M|D")],
         ArithmeticOp::Equal => comparator_template("JEQ", line_idx),
-        ArithmeticOp::GreaterThan => comparator_template("JGT", line_idx),
-        ArithmeticOp::LessThan => comparator_template("JLT", line_idx),
+        ArithmeticOp::GreaterThan => ordering_template("JGT", line_idx, options),
+        ArithmeticOp::LessThan => ordering_template("JLT", line_idx, options),
         _ => vec![],
     };
 
@@ -212,10 +318,324 @@ fn arithmetic_one_stack_val(op: &ArithmeticOp) -> Vec<Instruction> {
     instructs
 }
 
-fn arithmetic(op: &ArithmeticOp, line_idx: usize) -> Vec<Instruction> {
+/// The shared routine implementing an extended operation. `div` and `mod`
+/// share one routine, which leaves the remainder in `R13`.
+fn routine_op(op: &ArithmeticOp) -> ArithmeticOp {
+    match op {
+        ArithmeticOp::Modulo => ArithmeticOp::Divide,
+        _ => op.clone(),
+    }
+}
+
+fn routine_label(op: &ArithmeticOp) -> &'static str {
+    match routine_op(op) {
+        ArithmeticOp::Multiply => "VM_TRANSLATOR_MUL",
+        ArithmeticOp::Divide => "VM_TRANSLATOR_DIV",
+        ArithmeticOp::ShiftLeft => "VM_TRANSLATOR_SHL",
+        ArithmeticOp::ShiftRight => "VM_TRANSLATOR_SHR",
+        _ => panic!("{} has no routine", op),
+    }
+}
+
+/// The scratch variables the assembler allocates for an extended operation's
+/// routine, after the statics.
+pub fn routine_variables(op: &ArithmeticOp) -> &'static [&'static str] {
+    match routine_op(op) {
+        ArithmeticOp::Multiply => &["VM_TRANSLATOR_ACC", "VM_TRANSLATOR_BIT"],
+        ArithmeticOp::Divide => &[
+            "VM_TRANSLATOR_Y",
+            "VM_TRANSLATOR_X",
+            "VM_TRANSLATOR_ACC",
+            "VM_TRANSLATOR_REM",
+            "VM_TRANSLATOR_CNT",
+        ],
+        ArithmeticOp::ShiftRight => &["VM_TRANSLATOR_BIT", "VM_TRANSLATOR_ACC"],
+        _ => &[],
+    }
+}
+
+fn routine_return() -> Vec<Instruction> {
+    vec![a_sym("R15"), addr_assign("A", "M"), jmp_no_cond()]
+}
+
+/// `D = x * y`, by adding `x << i` for each bit `i` set in `y`.
+fn mul_routine() -> Vec<Instruction> {
+    let mut instructs = vec![
+        label("VM_TRANSLATOR_MUL"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "0"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("M", "1"),
+        label("VM_TRANSLATOR_MUL_LOOP"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("D", "M"),
+        a_sym("R14"),
+        addr_assign("D", "D&M"),
+        a_sym("VM_TRANSLATOR_MUL_SKIP"),
+        jmp("D", "JEQ"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "D+M"),
+        label("VM_TRANSLATOR_MUL_SKIP"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("D", "M"),
+        addr_assign("MD", "D+M"),
+        a_sym("VM_TRANSLATOR_MUL_LOOP"),
+        jmp("D", "JNE"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("D", "M"),
+    ];
+
+    instructs.append(&mut routine_return());
+    instructs
+}
+
+/// `D = x / y` and `R13 = x % y`, truncating towards zero. Divides the
+/// magnitudes one bit at a time, treating them as unsigned so that -32768
+/// works, then fixes up the signs. Dividing by zero gives zero.
+fn div_routine() -> Vec<Instruction> {
+    let mut instructs = vec![
+        label("VM_TRANSLATOR_DIV"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_DIV_ZERO"),
+        jmp("D", "JEQ"),
+        a_sym("VM_TRANSLATOR_Y"),
+        addr_assign("M", "D"),
+        a_sym("VM_TRANSLATOR_DIV_Y_POS"),
+        jmp("D", "JGE"),
+        a_sym("R14"),
+        addr_assign("M", "-M"),
+        label("VM_TRANSLATOR_DIV_Y_POS"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_X"),
+        addr_assign("M", "D"),
+        a_sym("VM_TRANSLATOR_DIV_X_POS"),
+        jmp("D", "JGE"),
+        a_sym("R13"),
+        addr_assign("M", "-M"),
+        label("VM_TRANSLATOR_DIV_X_POS"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "0"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("M", "0"),
+        a_const(&16),
+        addr_assign("D", "A"),
+        a_sym("VM_TRANSLATOR_CNT"),
+        addr_assign("M", "D"),
+        // Shift the next bit of x into the remainder.
+        label("VM_TRANSLATOR_DIV_LOOP"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("VM_TRANSLATOR_DIV_CMP"),
+        jmp("D", "JGE"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("M", "M+1"),
+        // Unsigned `rem >= y`, where y is at most 0x8000.
+        label("VM_TRANSLATOR_DIV_CMP"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_DIV_SUB"),
+        jmp("D", "JLT"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_DIV_NEXT"),
+        jmp("D", "JLT"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("D", "M"),
+        a_sym("R14"),
+        addr_assign("D", "D-M"),
+        a_sym("VM_TRANSLATOR_DIV_NEXT"),
+        jmp("D", "JLT"),
+        label("VM_TRANSLATOR_DIV_SUB"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("M", "M-D"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "M+1"),
+        label("VM_TRANSLATOR_DIV_NEXT"),
+        a_sym("VM_TRANSLATOR_CNT"),
+        addr_assign("MD", "M-1"),
+        a_sym("VM_TRANSLATOR_DIV_LOOP"),
+        jmp("D", "JGT"),
+        // The remainder takes the sign of x, the quotient is negative when
+        // the signs differ.
+        a_sym("VM_TRANSLATOR_X"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_DIV_X_SIGN"),
+        jmp("D", "JGE"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("M", "-M"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "-M"),
+        label("VM_TRANSLATOR_DIV_X_SIGN"),
+        a_sym("VM_TRANSLATOR_Y"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_DIV_DONE"),
+        jmp("D", "JGE"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "-M"),
+        label("VM_TRANSLATOR_DIV_DONE"),
+        a_sym("VM_TRANSLATOR_REM"),
+        addr_assign("D", "M"),
+        a_sym("R13"),
+        addr_assign("M", "D"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("D", "M"),
+    ];
+    instructs.append(&mut routine_return());
+
+    instructs.push(label("VM_TRANSLATOR_DIV_ZERO"));
+    instructs.push(a_sym("R13"));
+    instructs.push(addr_assign("M", "0"));
+    instructs.push(addr_assign("D", "0"));
+    instructs.append(&mut routine_return());
+
+    instructs
+}
+
+/// `D = x << y` by doubling x y times.
+fn shl_routine() -> Vec<Instruction> {
+    let mut instructs = vec![
+        label("VM_TRANSLATOR_SHL"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_SHL_DONE"),
+        jmp("D", "JLE"),
+        label("VM_TRANSLATOR_SHL_LOOP"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("R14"),
+        addr_assign("MD", "M-1"),
+        a_sym("VM_TRANSLATOR_SHL_LOOP"),
+        jmp("D", "JGT"),
+        label("VM_TRANSLATOR_SHL_DONE"),
+        a_sym("R13"),
+        addr_assign("D", "M"),
+    ];
+
+    instructs.append(&mut routine_return());
+    instructs
+}
+
+/// `D = x >>> y`, copying bit `i + y` of x to bit `i` of the result.
+fn shr_routine() -> Vec<Instruction> {
+    let mut instructs = vec![
+        label("VM_TRANSLATOR_SHR"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("M", "1"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_SHR_COPY"),
+        jmp("D", "JLE"),
+        label("VM_TRANSLATOR_SHR_SKIP"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("R14"),
+        addr_assign("MD", "M-1"),
+        a_sym("VM_TRANSLATOR_SHR_SKIP"),
+        jmp("D", "JGT"),
+        label("VM_TRANSLATOR_SHR_COPY"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "0"),
+        a_sym("R14"),
+        addr_assign("M", "1"),
+        label("VM_TRANSLATOR_SHR_LOOP"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_SHR_DONE"),
+        jmp("D", "JEQ"),
+        a_sym("R13"),
+        addr_assign("D", "D&M"),
+        a_sym("VM_TRANSLATOR_SHR_NEXT"),
+        jmp("D", "JEQ"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("M", "D|M"),
+        label("VM_TRANSLATOR_SHR_NEXT"),
+        a_sym("VM_TRANSLATOR_BIT"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("R14"),
+        addr_assign("D", "M"),
+        addr_assign("M", "D+M"),
+        a_sym("VM_TRANSLATOR_SHR_LOOP"),
+        jmp_no_cond(),
+        label("VM_TRANSLATOR_SHR_DONE"),
+        a_sym("VM_TRANSLATOR_ACC"),
+        addr_assign("D", "M"),
+    ];
+
+    instructs.append(&mut routine_return());
+    instructs
+}
+
+fn routine(op: &ArithmeticOp) -> Vec<Instruction> {
+    match routine_op(op) {
+        ArithmeticOp::Multiply => mul_routine(),
+        ArithmeticOp::Divide => div_routine(),
+        ArithmeticOp::ShiftLeft => shl_routine(),
+        ArithmeticOp::ShiftRight => shr_routine(),
+        _ => panic!("{} has no routine", op),
+    }
+}
+
+/// Calls an extended operation's routine with x in `R13`, y in `R14` and the
+/// return address in `R15`.
+fn extended_call(op: &ArithmeticOp, line_idx: usize) -> Vec<Instruction> {
+    let ret = format!("_ext_ret_{}", line_idx);
+    let mut instructs = vec![];
+
+    instructs.append(&mut stack_top());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym("R14"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.append(&mut dec_stack_ptr());
+    instructs.append(&mut stack_top());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym("R13"));
+    instructs.push(addr_assign("M", "D"));
+
+    instructs.push(a_sym(&ret));
+    instructs.push(addr_assign("D", "A"));
+    instructs.push(a_sym("R15"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.push(a_sym(routine_label(op)));
+    instructs.push(jmp_no_cond());
+    instructs.push(label(&ret));
+
+    if *op == ArithmeticOp::Modulo {
+        instructs.push(a_sym("R13"));
+        instructs.push(addr_assign("D", "M"));
+    }
+
+    instructs.append(&mut stack_top());
+    instructs.push(addr_assign("M", "D"));
+
+    instructs
+}
+
+fn arithmetic(op: &ArithmeticOp, line_idx: usize, options: &Options) -> Vec<Instruction> {
     match op {
         ArithmeticOp::Negate | ArithmeticOp::Not => arithmetic_one_stack_val(op),
-        _ => arithmetic_two_stack_val(op, line_idx),
+        _ if op.is_extended() => extended_call(op, line_idx),
+        _ => arithmetic_two_stack_val(op, line_idx, options),
     }
 }
 
@@ -256,8 +676,7 @@ fn emit_if_goto(label: &str, cur_func: &Option<String>) -> Vec<Instruction> {
 
 fn emit_func(func: &str, arg_cnt: &usize) -> Vec<Instruction> {
     let mut args = (0..*arg_cnt)
-        .map(|_| push(&Segment::Constant(0)))
-        .flatten()
+        .flat_map(|_| push(&Segment::Constant(0)))
         .collect::<Vec<Instruction>>();
 
     let mut instructs = vec![label(func)];
@@ -305,6 +724,75 @@ fn emit_call(
     instructs
 }
 
+/// Calls `func` in place of the current function: the arguments and the
+/// current frame's saved words are copied down to `ARG`, so the callee returns
+/// straight to our caller.
+fn emit_tail_call(func: &str, arg_cnt: &usize, line_idx: usize) -> Vec<Instruction> {
+    let words = (arg_cnt + 5) as i16;
+    let copy = format!("_tail_copy_{}", line_idx);
+    let mut instructs = vec![];
+
+    // Push the return address and saved LCL, ARG, THIS and THAT.
+    for offset in (1..=5).rev() {
+        instructs.push(a_sym("LCL"));
+        instructs.push(addr_assign("D", "M"));
+        instructs.push(a_const(&offset));
+        instructs.push(addr_assign("A", "D-A"));
+        instructs.push(addr_assign("D", "M"));
+        instructs.push(sp());
+        instructs.push(addr_assign("A", "M"));
+        instructs.push(addr_assign("M", "D"));
+        instructs.append(&mut inc_stack_ptr());
+    }
+
+    // R13 = SP - words, R14 = ARG, R15 = words
+    instructs.push(sp());
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_const(&words));
+    instructs.push(addr_assign("D", "D-A"));
+    instructs.push(a_sym("R13"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.push(a_sym("ARG"));
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym("R14"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.push(a_const(&words));
+    instructs.push(addr_assign("D", "A"));
+    instructs.push(a_sym("R15"));
+    instructs.push(addr_assign("M", "D"));
+
+    // Copy upwards, which is safe as the destination is below the source.
+    instructs.push(label(&copy));
+    instructs.push(a_sym("R13"));
+    instructs.push(addr_assign("A", "M"));
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_sym("R14"));
+    instructs.push(addr_assign("A", "M"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.push(a_sym("R13"));
+    instructs.push(addr_assign("M", "M+1"));
+    instructs.push(a_sym("R14"));
+    instructs.push(addr_assign("M", "M+1"));
+    instructs.push(a_sym("R15"));
+    instructs.push(addr_assign("MD", "M-1"));
+    instructs.push(a_sym(&copy));
+    instructs.push(jmp("D", "JGT"));
+
+    // LCL = SP = ARG + words
+    instructs.push(a_sym("ARG"));
+    instructs.push(addr_assign("D", "M"));
+    instructs.push(a_const(&words));
+    instructs.push(addr_assign("D", "D+A"));
+    instructs.push(a_sym("LCL"));
+    instructs.push(addr_assign("M", "D"));
+    instructs.push(sp());
+    instructs.push(addr_assign("M", "D"));
+
+    instructs.append(&mut emit_goto(func, &None));
+
+    instructs
+}
+
 fn frame_sub(dest: &str, offset: usize) -> Vec<Instruction> {
     vec![
         a_sym("R14"),
@@ -357,35 +845,346 @@ fn emit_return() -> Vec<Instruction> {
     instructs
 }
 
+/// Where checked code writes the code of the error that stopped it.
+pub const ERROR_ADDR: i16 = 2047;
+
+/// Error codes written to `ERROR_ADDR`.
+pub const ERROR_STACK_OVERFLOW: i16 = 1;
+pub const ERROR_STACK_UNDERFLOW: i16 = 2;
+pub const ERROR_THAT_OUT_OF_RANGE: i16 = 3;
+pub const ERROR_RETURN_DEPTH: i16 = 4;
+
+/// The highest address `that` may point to, the keyboard register.
+const MAX_THAT_ADDR: i16 = 24576;
+
+const ERROR_HANDLERS: [(&str, i16); 4] = [
+    ("VM_TRANSLATOR_STACK_OVERFLOW", ERROR_STACK_OVERFLOW),
+    ("VM_TRANSLATOR_STACK_UNDERFLOW", ERROR_STACK_UNDERFLOW),
+    ("VM_TRANSLATOR_THAT_OUT_OF_RANGE", ERROR_THAT_OUT_OF_RANGE),
+    ("VM_TRANSLATOR_RETURN_DEPTH", ERROR_RETURN_DEPTH),
+];
+
+/// Runtime checks emitted around each command.
+#[derive(Clone)]
+pub struct Checks {
+    /// The highest value `SP` may take. The default keeps the stack below
+    /// `ERROR_ADDR`.
+    pub stack_limit: i16,
+}
+
+impl Default for Checks {
+    fn default() -> Self {
+        Checks {
+            stack_limit: ERROR_ADDR,
+        }
+    }
+}
+
+fn error_if(comp: &str, jump: &str, handler: &str) -> Vec<Instruction> {
+    vec![addr_assign("D", comp), a_sym(handler), jmp("D", jump)]
+}
+
+/// Jumps to `handler` when `SP + extra` exceeds the stack limit.
+fn overflow_guard(checks: &Checks, extra: i16) -> Vec<Instruction> {
+    let handler = ERROR_HANDLERS[0].0;
+
+    // A limit below `extra` is exceeded whatever SP is.
+    let max = match checks.stack_limit.checked_sub(extra) {
+        Some(max) if max >= 0 => max,
+        _ => return vec![a_sym(handler), jmp_no_cond()],
+    };
+
+    let mut instructs = vec![sp(), addr_assign("D", "M"), a_const(&max)];
+    instructs.append(&mut error_if("D-A", "JGT", handler));
+
+    instructs
+}
+
+/// Jumps to the handler when `SP - LCL` differs from `depth` as `jump` says.
+fn depth_guard(depth: usize, jump: &str, handler: &str) -> Vec<Instruction> {
+    let mut instructs = vec![
+        a_sym("LCL"),
+        addr_assign("D", "M"),
+        a_const(&(depth as i16)),
+        addr_assign("D", "D+A"),
+        sp(),
+    ];
+    instructs.append(&mut error_if("M-D", jump, handler));
+
+    instructs
+}
+
+fn that_guard(offset: &i16) -> Vec<Instruction> {
+    let handler = ERROR_HANDLERS[2].0;
+    let mut instructs = vec![a_sym("THAT"), addr_assign("D", "M"), a_const(offset)];
+    instructs.append(&mut error_if("D+A", "JLT", handler));
+    instructs.push(a_const(&MAX_THAT_ADDR));
+    instructs.append(&mut error_if("D-A", "JGT", handler));
+
+    instructs
+}
+
+/// The values a command pops from the stack.
+fn pops(cmd: &Command) -> usize {
+    match cmd {
+        Command::Pop(_) | Command::IfGoto(_) => 1,
+        Command::Arithmetic(ArithmeticOp::Negate) | Command::Arithmetic(ArithmeticOp::Not) => 1,
+        Command::Arithmetic(_) => 2,
+        Command::Call(_, arg_cnt) | Command::TailCall(_, arg_cnt) => *arg_cnt,
+        Command::Return => 1,
+        _ => 0,
+    }
+}
+
+/// Guards to run before and after `cmd`. `locals` is the current function's
+/// local count, when inside one.
+fn guards(
+    cmd: &Command,
+    checks: &Checks,
+    locals: Option<usize>,
+) -> (Vec<Instruction>, Vec<Instruction>) {
+    let mut before = vec![];
+    let mut after = vec![];
+
+    if let Some(locals) = locals {
+        match cmd {
+            Command::Return => {
+                before.append(&mut depth_guard(locals + 1, "JNE", ERROR_HANDLERS[3].0))
+            }
+            Command::Function(..) => {}
+            _ if pops(cmd) > 0 => before.append(&mut depth_guard(
+                locals + pops(cmd),
+                "JLT",
+                ERROR_HANDLERS[1].0,
+            )),
+            _ => {}
+        }
+    }
+
+    match cmd {
+        Command::Push(Segment::That(offset)) | Command::Pop(Segment::That(offset)) => {
+            before.append(&mut that_guard(offset))
+        }
+        Command::Call(..) | Command::TailCall(..) => before.append(&mut overflow_guard(checks, 5)),
+        _ => {}
+    }
+
+    if let Command::Push(_) | Command::Function(..) = cmd {
+        after.append(&mut overflow_guard(checks, 0));
+    }
+
+    (before, after)
+}
+
+fn error_handlers() -> Vec<Instruction> {
+    let halt = "VM_TRANSLATOR_ERROR_HALT";
+    let mut instructs = vec![];
+
+    for (handler, code) in ERROR_HANDLERS.iter() {
+        instructs.push(label(handler));
+        instructs.push(a_const(code));
+        instructs.push(addr_assign("D", "A"));
+        instructs.push(a_const(&ERROR_ADDR));
+        instructs.push(addr_assign("M", "D"));
+        instructs.push(a_sym(halt));
+        instructs.push(jmp_no_cond());
+    }
+
+    instructs.push(label(halt));
+    instructs.push(a_sym(halt));
+    instructs.push(jmp_no_cond());
+
+    instructs
+}
+
+/// Code run before the program to set up the stack and call the entry function.
+#[derive(Clone)]
+pub struct Bootstrap {
+    pub stack_base: i16,
+    pub entry: String,
+    /// Sets `LCL`, `ARG`, `THIS` and `THAT` to -1, -2, -3 and -4 like the
+    /// course test scripts, so stray segment accesses stand out.
+    pub sentinels: bool,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Bootstrap {
+            stack_base: 256,
+            entry: "Sys.init".to_string(),
+            sentinels: false,
+        }
+    }
+}
+
+/// The form of the translated program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emit {
+    /// Hack assembly.
+    Asm,
+    /// Assembled machine code, one binary word per line.
+    Hack,
+}
+
+impl Emit {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Asm => "asm",
+            Self::Hack => "hack",
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asm" => Ok(Self::Asm),
+            "hack" => Ok(Self::Hack),
+            _ => Err(format!("expected asm or hack, found `{}`", s)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub bootstrap: Option<Bootstrap>,
+    /// Compares with a single subtraction, which is wrong when it overflows.
+    pub fast_compare: bool,
+    /// Precedes each expansion with a comment naming its VM command.
+    pub annotate: bool,
+    pub emit: Emit,
+    /// Guards against stack and `that` misuse, halting with an error code in
+    /// `ERROR_ADDR`.
+    pub checks: Option<Checks>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            bootstrap: Some(Bootstrap::default()),
+            fast_compare: false,
+            annotate: false,
+            emit: Emit::Asm,
+            checks: None,
+        }
+    }
+}
+
 pub struct CodeWriter<T: Write> {
     writer: BufWriter<T>,
+    options: Options,
     cur_line_idx: usize,
     cur_ret_count: usize,
     cur_func: Option<String>,
+    /// The current function's local count, unknown outside functions.
+    cur_locals: Option<usize>,
+    rom_addr: usize,
+    source_map: SourceMap,
+    /// Everything written so far, which `close` assembles when emitting
+    /// machine code.
+    program: Vec<Instruction>,
+    /// Extended operations whose routines are needed, in order of first use.
+    routines: Vec<ArithmeticOp>,
 }
 
 impl<T: Write> CodeWriter<T> {
-    pub fn new(writer: BufWriter<T>, no_sys_init: bool) -> Self {
+    pub fn new(writer: BufWriter<T>, options: Options) -> Self {
         let mut writer = CodeWriter {
             writer,
+            options,
             cur_line_idx: 0,
             cur_ret_count: 0,
             cur_func: None,
+            cur_locals: None,
+            rom_addr: 0,
+            source_map: SourceMap::default(),
+            program: vec![],
+            routines: vec![],
         };
 
-        if !no_sys_init {
-            let out = instruct_vec_str(&assembly_header());
-            writeln!(writer.writer, "{}", out).unwrap();
+        if let Some(bootstrap) = &writer.options.bootstrap {
+            let mut header = vec![];
+            if writer.options.annotate {
+                header.push(comment(&format!(
+                    "bootstrap: SP = {}, call {}",
+                    bootstrap.stack_base, bootstrap.entry
+                )));
+            }
+            header.append(&mut assembly_header(bootstrap));
+
+            writer.map(&header, None, "bootstrap");
+            writer.emit(header).unwrap();
         }
 
         writer
     }
 
-    pub fn write(&mut self, cmd: &Command) -> std::io::Result<()> {
-        let output = match cmd {
+    fn emit(&mut self, instructs: Vec<Instruction>) -> std::io::Result<()> {
+        if self.options.emit == Emit::Asm {
+            writeln!(self.writer, "{}", instruct_vec_str(&instructs))?;
+        }
+
+        self.program.extend(instructs);
+        Ok(())
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    /// Records that `instructs` come from `cmd` and advances the ROM address.
+    fn map(&mut self, instructs: &[Instruction], pos: Option<&Position>, cmd: &str) {
+        let start = self.rom_addr;
+        self.rom_addr += rom_len(instructs);
+
+        self.source_map.push(Entry {
+            start,
+            end: self.rom_addr,
+            pos: pos.cloned(),
+            func: self.cur_func.clone(),
+            cmd: cmd.to_string(),
+        });
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    fn annotation(&self, cmd: &Command, pos: Option<&Position>) -> Vec<Instruction> {
+        let mut instructs = vec![];
+
+        if let Command::Function(func, _) = cmd {
+            instructs.push(comment(&format!("==== function {} ====", func)));
+        }
+
+        instructs.push(match pos {
+            Some(pos) => comment(&format!("{} {}", pos, cmd)),
+            None => comment(&cmd.to_string()),
+        });
+
+        instructs
+    }
+
+    /// Lowers a command from `pos` to the instructions implementing it.
+    pub fn lower(&mut self, cmd: &Command, pos: Option<&Position>) -> Vec<Instruction> {
+        let mut output = if self.options.annotate {
+            self.annotation(cmd, pos)
+        } else {
+            vec![]
+        };
+        let body_start = output.len();
+
+        output.append(&mut match cmd {
             Command::Push(seg) => push(seg),
             Command::Pop(seg) => pop(seg),
-            Command::Arithmetic(op) => arithmetic(op, self.cur_line_idx),
+            Command::Arithmetic(op) => {
+                if op.is_extended() && !self.routines.contains(&routine_op(op)) {
+                    self.routines.push(routine_op(op));
+                }
+                arithmetic(op, self.cur_line_idx, &self.options)
+            }
             Command::Label(label) => vec![emit_label(label, &self.cur_func)],
             Command::Goto(label) => emit_goto(label, &self.cur_func),
             Command::IfGoto(label) => emit_if_goto(label, &self.cur_func),
@@ -394,29 +1193,83 @@ impl<T: Write> CodeWriter<T> {
                 self.cur_ret_count += 1;
                 out
             }
-            Command::Function(func, arg_cnt) => {
+            Command::Function(func, local_cnt) => {
                 self.cur_func = Some(func.to_string());
+                self.cur_locals = Some(*local_cnt);
                 self.cur_ret_count = 0;
-                emit_func(func, arg_cnt)
+                emit_func(func, local_cnt)
             }
+            Command::TailCall(func, arg_cnt) => emit_tail_call(func, arg_cnt, self.cur_line_idx),
             Command::Return => emit_return(),
-        };
+        });
+
+        if let Some(checks) = &self.options.checks {
+            let (mut before, mut after) = guards(cmd, checks, self.cur_locals);
+            output.splice(body_start..body_start, before.drain(..));
+            output.append(&mut after);
+        }
 
-        writeln!(self.writer, "{}", instruct_vec_str(&output))?;
-        self.writer.flush()?;
         self.cur_line_idx += 1;
+        output
+    }
+
+    pub fn write(&mut self, cmd: &Command) -> std::io::Result<()> {
+        self.write_at(cmd, None)
+    }
+
+    pub fn write_at(&mut self, cmd: &Command, pos: Option<&Position>) -> std::io::Result<()> {
+        let output = self.lower(cmd, pos);
+        self.map(&output, pos, &cmd.to_string());
+
+        self.emit(output)?;
+        self.writer.flush()?;
         Ok(())
     }
 
     pub fn on_new_file(&mut self) {
         self.cur_func = None;
+        self.cur_locals = None;
         self.cur_ret_count = 0;
     }
 
     pub fn close(&mut self) -> std::io::Result<()> {
         // Write infinite loop.
-        let end_loop = assembly_footer();
-        writeln!(self.writer, "{}", instruct_vec_str(&end_loop))?;
+        let mut end_loop = vec![];
+        if self.options.annotate {
+            end_loop.push(comment("end of program"));
+        }
+        end_loop.append(&mut assembly_footer());
+        self.cur_func = None;
+        self.map(&end_loop, None, "end loop");
+        self.emit(end_loop)?;
+
+        for op in self.routines.clone() {
+            let mut instructs = vec![];
+            if self.options.annotate {
+                instructs.push(comment(&format!("routine for {}", op)));
+            }
+            instructs.append(&mut routine(&op));
+
+            self.map(&instructs, None, &format!("{} routine", op));
+            self.emit(instructs)?;
+        }
+
+        if self.options.checks.is_some() {
+            let mut instructs = vec![];
+            if self.options.annotate {
+                instructs.push(comment("runtime check failures"));
+            }
+            instructs.append(&mut error_handlers());
+
+            self.map(&instructs, None, "error handlers");
+            self.emit(instructs)?;
+        }
+
+        if self.options.emit == Emit::Hack {
+            for word in assemble::assemble(&self.program) {
+                writeln!(self.writer, "{}", word)?;
+            }
+        }
 
         self.writer.flush()?;
         Ok(())
@@ -426,24 +1279,34 @@ impl<T: Write> CodeWriter<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::emulator::Cpu;
+    use crate::interpreter::Vm;
+    use crate::ir::{Module, Program};
+    use crate::pass::tco::Tco;
+    use crate::pass::Pass;
+    use std::io::BufReader;
 
     fn test_iter(cmds: &[Command], expected_body: Option<&str>) {
+        test_iter_with(cmds, expected_body, Options::default());
+    }
+
+    fn test_iter_with(cmds: &[Command], expected_body: Option<&str>, options: Options) {
         let expected = match expected_body {
             Some(body) => format!(
                 "{}\n{}\n{}\n",
-                instruct_vec_str(&assembly_header()),
+                instruct_vec_str(&assembly_header(&Bootstrap::default())),
                 body,
                 instruct_vec_str(&assembly_footer())
             ),
             None => format!(
                 "{}\n{}\n",
-                instruct_vec_str(&assembly_header()),
+                instruct_vec_str(&assembly_header(&Bootstrap::default())),
                 instruct_vec_str(&assembly_footer())
             ),
         };
 
         let buf_writer = BufWriter::new(Vec::new());
-        let mut writer = CodeWriter::new(buf_writer, false);
+        let mut writer = CodeWriter::new(buf_writer, options);
 
         for cmd in cmds {
             writer.write(cmd).unwrap();
@@ -586,7 +1449,7 @@ mod tests {
     }
 
     #[test]
-    fn test_gt() {
+    fn test_gt_fast() {
         let cmd = [Command::Arithmetic(ArithmeticOp::GreaterThan)];
         let expected = [
             "@SP",
@@ -614,11 +1477,18 @@ mod tests {
         ]
         .join("\n");
 
-        test_iter(&cmd, Some(&expected));
+        test_iter_with(
+            &cmd,
+            Some(&expected),
+            Options {
+                fast_compare: true,
+                ..Options::default()
+            },
+        );
     }
 
     #[test]
-    fn test_lt() {
+    fn test_lt_fast() {
         let cmd = [Command::Arithmetic(ArithmeticOp::LessThan)];
         let expected = [
             "@SP",
@@ -646,7 +1516,447 @@ mod tests {
         ]
         .join("\n");
 
-        test_iter(&cmd, Some(&expected));
+        test_iter_with(
+            &cmd,
+            Some(&expected),
+            Options {
+                fast_compare: true,
+                ..Options::default()
+            },
+        );
+    }
+
+    const BOUNDARY_VALUES: [i16; 11] = [
+        i16::MIN,
+        i16::MIN + 1,
+        -16384,
+        -2,
+        -1,
+        0,
+        1,
+        2,
+        16384,
+        i16::MAX - 1,
+        i16::MAX,
+    ];
+
+    /// Runs `x op y` on the CPU emulator and returns the value left on the stack.
+    fn emulate_op(op: &ArithmeticOp, x: i16, y: i16, options: &Options) -> i16 {
+        let mut program = arithmetic(op, 0, options);
+        program.append(&mut assembly_footer());
+        if op.is_extended() {
+            program.append(&mut routine(op));
+        }
+
+        let mut cpu = Cpu::new(&program);
+        cpu.ram_mut()[..2].copy_from_slice(&[258, 0]);
+        cpu.ram_mut()[256..258].copy_from_slice(&[x, y]);
+        assert!(cpu.run(1_000_000));
+        assert_eq!(257, cpu.ram()[0]);
+
+        cpu.ram()[256]
+    }
+
+    fn interpret_op(op: &ArithmeticOp, x: i16, y: i16) -> i16 {
+        let program = [
+            Command::Push(Segment::Static("test".to_string(), 0)),
+            Command::Push(Segment::Static("test".to_string(), 1)),
+            Command::Arithmetic(op.clone()),
+        ];
+
+        let mut vm = Vm::new(&program);
+        vm.ram_mut()[0] = 256;
+        vm.ram_mut()[16..18].copy_from_slice(&[x, y]);
+        assert!(vm.run(1000));
+
+        vm.ram()[256]
+    }
+
+    #[test]
+    fn test_comparison_boundaries() {
+        let ops = [
+            ArithmeticOp::Equal,
+            ArithmeticOp::GreaterThan,
+            ArithmeticOp::LessThan,
+        ];
+
+        for op in ops.iter() {
+            for x in BOUNDARY_VALUES.iter() {
+                for y in BOUNDARY_VALUES.iter() {
+                    assert_eq!(
+                        interpret_op(op, *x, *y),
+                        emulate_op(op, *x, *y, &Options::default()),
+                        "{} {:?} {}",
+                        x,
+                        op,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_extended_boundaries() {
+        let ops = [
+            ArithmeticOp::Multiply,
+            ArithmeticOp::Divide,
+            ArithmeticOp::Modulo,
+            ArithmeticOp::ShiftLeft,
+            ArithmeticOp::ShiftRight,
+        ];
+        let values = BOUNDARY_VALUES
+            .iter()
+            .chain([3, 7, 15, 16, 100].iter())
+            .copied()
+            .collect::<Vec<i16>>();
+
+        for op in ops.iter() {
+            for x in values.iter() {
+                for y in values.iter() {
+                    assert_eq!(
+                        interpret_op(op, *x, *y),
+                        emulate_op(op, *x, *y, &Options::default()),
+                        "{} {:?} {}",
+                        x,
+                        op,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    fn translate(cmds: &[Command], options: Options) -> String {
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), options);
+        for cmd in cmds {
+            writer.write(cmd).unwrap();
+        }
+        writer.close().unwrap();
+
+        let bytes = writer.writer.into_inner().unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_routines_emitted_once() {
+        let cmds = [
+            Command::Push(Segment::Constant(6)),
+            Command::Push(Segment::Constant(4)),
+            Command::Arithmetic(ArithmeticOp::Divide),
+            Command::Push(Segment::Constant(3)),
+            Command::Arithmetic(ArithmeticOp::Modulo),
+        ];
+        let options = Options {
+            bootstrap: None,
+            ..Options::default()
+        };
+
+        let asm = translate(&cmds, options.clone());
+        assert_eq!(1, asm.matches("(VM_TRANSLATOR_DIV)").count());
+        assert!(!asm.contains("(VM_TRANSLATOR_MUL)"));
+
+        let hack = translate(
+            &cmds,
+            Options {
+                emit: Emit::Hack,
+                ..options
+            },
+        );
+        let rom = hack
+            .lines()
+            .map(|word| u16::from_str_radix(word, 2).unwrap())
+            .collect::<Vec<u16>>();
+
+        let mut cpu = Cpu::load(rom);
+        cpu.ram_mut()[0] = 256;
+        assert!(cpu.run(10_000));
+        assert_eq!(257, cpu.ram()[0]);
+        assert_eq!(1, cpu.ram()[256]);
+    }
+
+    #[test]
+    fn test_tail_call() {
+        let input = "function Sys.init 0\npush constant 0\npush constant 1000\n\
+                     call Main.sum 2\npop temp 0\nlabel END\ngoto END\n\
+                     function Main.sum 1\npush argument 1\nif-goto REC\npush argument 0\n\
+                     return\nlabel REC\npush argument 0\npush argument 1\nadd\n\
+                     push argument 1\npush constant 1\nsub\ncall Main.sum 2\nreturn\n";
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+        Tco.run(&mut program).unwrap();
+        let cmds = program
+            .commands()
+            .map(|located| located.cmd.clone())
+            .collect::<Vec<Command>>();
+
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), Options::default());
+        let mut instructs = vec![];
+        for cmd in &cmds {
+            instructs.append(&mut writer.lower(cmd, None));
+        }
+        instructs.append(&mut assembly_footer());
+
+        let mut header = assembly_header(&Bootstrap::default());
+        header.append(&mut instructs);
+
+        let mut cpu = Cpu::new(&header);
+        let mut max_sp = 0;
+        while !cpu.halted() {
+            cpu.step();
+            max_sp = max_sp.max(cpu.ram()[0]);
+        }
+
+        let mut vm = Vm::new(&cmds);
+        vm.ram_mut()[0] = 256;
+        assert!(vm.run(100_000));
+
+        assert_eq!(500500u32 as i16, cpu.ram()[5]);
+        assert_eq!(vm.ram()[5], cpu.ram()[5]);
+        assert!(max_sp < 300, "stack grew to {}", max_sp);
+    }
+
+    /// Runs `input` as checked code and returns the error code it halts with.
+    fn checked_error(input: &str, stack_limit: i16) -> i16 {
+        let cmds = Module::parse(BufReader::new(input.as_bytes()), "Main")
+            .unwrap()
+            .commands
+            .into_iter()
+            .map(|located| located.cmd)
+            .collect::<Vec<Command>>();
+        checked_run(&cmds, stack_limit)
+    }
+
+    fn checked_run(cmds: &[Command], stack_limit: i16) -> i16 {
+        let options = Options {
+            emit: Emit::Hack,
+            checks: Some(Checks { stack_limit }),
+            ..Options::default()
+        };
+        let rom = translate(cmds, options)
+            .lines()
+            .map(|word| u16::from_str_radix(word, 2).unwrap())
+            .collect::<Vec<u16>>();
+
+        let mut cpu = Cpu::load(rom);
+        assert!(cpu.run(100_000));
+        cpu.ram()[ERROR_ADDR as usize]
+    }
+
+    #[test]
+    fn test_checked() {
+        let ok = "function Sys.init 1\npush constant 3000\npop pointer 1\npush constant 4\n\
+                  pop that 2\ncall Main.f 0\npop local 0\nlabel END\ngoto END\n\
+                  function Main.f 0\npush constant 1\nreturn\n";
+        assert_eq!(0, checked_error(ok, ERROR_ADDR));
+
+        let overflow = "function Sys.init 0\nlabel L\npush constant 1\ngoto L\n";
+        assert_eq!(ERROR_STACK_OVERFLOW, checked_error(overflow, 400));
+
+        let deep = "function Sys.init 0\ncall Sys.init 0\nreturn\n";
+        assert_eq!(ERROR_STACK_OVERFLOW, checked_error(deep, 400));
+        assert_eq!(ERROR_STACK_OVERFLOW, checked_error(deep, 3));
+        assert_eq!(ERROR_STACK_OVERFLOW, checked_error(deep, i16::MIN));
+
+        let underflow = "function Sys.init 1\npush constant 1\nadd\n";
+        assert_eq!(ERROR_STACK_UNDERFLOW, checked_error(underflow, ERROR_ADDR));
+
+        let that = "function Sys.init 0\npush constant 24570\npop pointer 1\npush that 7\n";
+        assert_eq!(ERROR_THAT_OUT_OF_RANGE, checked_error(that, ERROR_ADDR));
+
+        let depth = "function Sys.init 0\ncall Main.f 0\nlabel END\ngoto END\n\
+                     function Main.f 0\npush constant 1\npush constant 2\nreturn\n";
+        assert_eq!(ERROR_RETURN_DEPTH, checked_error(depth, ERROR_ADDR));
+    }
+
+    /// A tail call pushes the frame before copying it down, so it needs the
+    /// same headroom as a call.
+    #[test]
+    fn test_checked_tail_call() {
+        let pushes = "push constant 1\n".repeat(13);
+        let input = format!(
+            "function Sys.init 0\ncall Main.f 0\nlabel END\ngoto END\n\
+             function Main.f 0\n{}call Main.g 0\nreturn\n\
+             function Main.g 0\npush constant 1\nreturn\n",
+            pushes
+        );
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+        Tco.run(&mut program).unwrap();
+        let cmds = program
+            .commands()
+            .map(|located| located.cmd.clone())
+            .collect::<Vec<Command>>();
+        assert!(cmds.iter().any(|cmd| matches!(cmd, Command::TailCall(..))));
+
+        assert_eq!(0, checked_run(&cmds, 285));
+        assert_eq!(ERROR_STACK_OVERFLOW, checked_run(&cmds, 280));
+    }
+
+    #[test]
+    fn test_fast_compare_overflows() {
+        let options = Options {
+            fast_compare: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            0,
+            emulate_op(&ArithmeticOp::GreaterThan, 32767, -2, &options)
+        );
+        assert_eq!(
+            -1,
+            emulate_op(&ArithmeticOp::GreaterThan, 32767, -2, &Options::default())
+        );
+    }
+
+    #[test]
+    fn test_bootstrap_options() {
+        let bootstrap = Bootstrap {
+            stack_base: 300,
+            entry: "Main.main".to_string(),
+            sentinels: true,
+        };
+        let header = instruct_vec_str(&assembly_header(&bootstrap));
+
+        assert!(header.starts_with(
+            &[
+                "@300", "D=A", "@SP", "M=D", "@1", "D=-A", "@LCL", "M=D", "@2", "D=-A", "@ARG",
+                "M=D", "@3", "D=-A", "@THIS", "M=D", "@4", "D=-A", "@THAT", "M=D",
+            ]
+            .join("\n")
+        ));
+        assert!(header.ends_with("@Main.main\n0;JMP\n(_internal_vm_translator$ret.0)"));
+    }
+
+    #[test]
+    fn test_no_bootstrap() {
+        let options = Options {
+            bootstrap: None,
+            ..Options::default()
+        };
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), options);
+        writer.close().unwrap();
+
+        let bytes = writer.writer.into_inner().unwrap();
+        let expected = format!("{}\n", instruct_vec_str(&assembly_footer()));
+        assert_eq!(expected, String::from_utf8(bytes).unwrap());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let options = Options {
+            bootstrap: None,
+            annotate: true,
+            ..Options::default()
+        };
+        let pos = Position {
+            file: "Main.vm".to_string(),
+            line: 12,
+        };
+
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), options);
+        writer
+            .write_at(&Command::Function("Main.main".to_string(), 0), Some(&pos))
+            .unwrap();
+        writer.write(&Command::Push(Segment::Local(0))).unwrap();
+        writer.close().unwrap();
+
+        let bytes = writer.writer.into_inner().unwrap();
+        let actual = String::from_utf8(bytes).unwrap();
+        let lines = actual.lines().collect::<Vec<&str>>();
+
+        assert_eq!(
+            [
+                "// ==== function Main.main ====",
+                "// Main.vm:12 function Main.main 0",
+                "(Main.main)",
+                "// push local 0",
+                "@0"
+            ],
+            lines[..5]
+        );
+        assert!(actual.contains("// end of program\n(VM_TRANSLATOR_END_LOOP)"));
+    }
+
+    #[test]
+    fn test_annotate_bootstrap() {
+        let options = Options {
+            annotate: true,
+            ..Options::default()
+        };
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), options);
+        writer.close().unwrap();
+
+        let bytes = writer.writer.into_inner().unwrap();
+        let actual = String::from_utf8(bytes).unwrap();
+
+        assert!(actual.starts_with("// bootstrap: SP = 256, call Sys.init\n@256\n"));
+    }
+
+    #[test]
+    fn test_source_map() {
+        let pos = Position {
+            file: "Main.vm".to_string(),
+            line: 2,
+        };
+        let cmds = [
+            Command::Function("Main.main".to_string(), 0),
+            Command::Push(Segment::Constant(7)),
+        ];
+
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), Options::default());
+        writer.write(&cmds[0]).unwrap();
+        writer.write_at(&cmds[1], Some(&pos)).unwrap();
+        writer.close().unwrap();
+
+        let header_len = rom_len(&assembly_header(&Bootstrap::default()));
+        let map = writer.source_map();
+        let entry = map.lookup(header_len).unwrap();
+
+        assert_eq!("bootstrap", map.lookup(0).unwrap().cmd);
+        assert_eq!(header_len..header_len + 7, entry.start..entry.end);
+        assert_eq!(Some(pos), entry.pos);
+        assert_eq!(Some("Main.main".to_string()), entry.func);
+        assert_eq!("push constant 7", entry.cmd);
+        assert_eq!("end loop", map.lookup(header_len + 7).unwrap().cmd);
+    }
+
+    #[test]
+    fn test_emit_hack() {
+        let cmds = [
+            Command::Function("Sys.init".to_string(), 0),
+            Command::Push(Segment::Constant(7)),
+            Command::Push(Segment::Constant(8)),
+            Command::Arithmetic(ArithmeticOp::Add),
+        ];
+        let options = Options {
+            emit: Emit::Hack,
+            annotate: true,
+            ..Options::default()
+        };
+
+        let mut writer = CodeWriter::new(BufWriter::new(Vec::new()), options);
+        for cmd in &cmds {
+            writer.write(cmd).unwrap();
+        }
+        writer.close().unwrap();
+        let words = writer.source_map().entries.last().unwrap().end;
+
+        let bytes = writer.writer.into_inner().unwrap();
+        let rom = String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|word| u16::from_str_radix(word, 2).unwrap())
+            .collect::<Vec<u16>>();
+
+        assert_eq!(words, rom.len());
+
+        let mut cpu = Cpu::load(rom);
+        assert!(cpu.run(1000));
+        assert_eq!(15, cpu.ram()[cpu.ram()[0] as usize - 1]);
     }
 
     #[test]
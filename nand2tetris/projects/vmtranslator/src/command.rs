@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq)]
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Segment {
     Argument(i16),
     Local(i16),
@@ -12,7 +14,7 @@ pub enum Segment {
     NamedPtr(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ArithmeticOp {
     Add,
     Subtract,
@@ -23,9 +25,25 @@ pub enum ArithmeticOp {
     And,
     Or,
     Not,
+    Multiply,
+    Divide,
+    Modulo,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl ArithmeticOp {
+    /// Determines if the operation is one of our additions to the standard
+    /// VM language.
+    pub fn is_extended(&self) -> bool {
+        matches!(
+            self,
+            Self::Multiply | Self::Divide | Self::Modulo | Self::ShiftLeft | Self::ShiftRight
+        )
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Command {
     Arithmetic(ArithmeticOp),
     Push(Segment),
@@ -36,4 +54,64 @@ pub enum Command {
     Function(String, usize),
     Return,
     Call(String, usize),
+    /// A `call` followed by `return`, which reuses the caller's frame. Only
+    /// produced by the `tco` pass.
+    TailCall(String, usize),
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Argument(val) => write!(f, "argument {}", val),
+            Self::Local(val) => write!(f, "local {}", val),
+            Self::Static(_, val) => write!(f, "static {}", val),
+            Self::Constant(val) => write!(f, "constant {}", val),
+            Self::This(val) => write!(f, "this {}", val),
+            Self::That(val) => write!(f, "that {}", val),
+            Self::Pointer(val) => write!(f, "pointer {}", val),
+            Self::Temp(val) => write!(f, "temp {}", val),
+            Self::Named(name) => write!(f, "named {}", name),
+            Self::NamedPtr(name) => write!(f, "address {}", name),
+        }
+    }
+}
+
+impl fmt::Display for ArithmeticOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let out = match self {
+            Self::Add => "add",
+            Self::Subtract => "sub",
+            Self::Negate => "neg",
+            Self::Equal => "eq",
+            Self::GreaterThan => "gt",
+            Self::LessThan => "lt",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Not => "not",
+            Self::Multiply => "mul",
+            Self::Divide => "div",
+            Self::Modulo => "mod",
+            Self::ShiftLeft => "shl",
+            Self::ShiftRight => "shr",
+        };
+
+        write!(f, "{}", out)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Arithmetic(op) => write!(f, "{}", op),
+            Self::Push(seg) => write!(f, "push {}", seg),
+            Self::Pop(seg) => write!(f, "pop {}", seg),
+            Self::Label(lbl) => write!(f, "label {}", lbl),
+            Self::Goto(lbl) => write!(f, "goto {}", lbl),
+            Self::IfGoto(lbl) => write!(f, "if-goto {}", lbl),
+            Self::Function(func, local_cnt) => write!(f, "function {} {}", func, local_cnt),
+            Self::Return => write!(f, "return"),
+            Self::Call(func, arg_cnt) => write!(f, "call {} {}", func, arg_cnt),
+            Self::TailCall(func, arg_cnt) => write!(f, "tail-call {} {}", func, arg_cnt),
+        }
+    }
 }
@@ -0,0 +1,196 @@
+use crate::instruct::Instruction;
+use assembler::assemble;
+
+pub const RAM_SIZE: usize = 32768;
+
+/// The machine word for `0;JMP`.
+const JMP_NO_COND: u16 = 0b1110_1010_1000_0111;
+
+/// Evaluates the ALU's `zx nx zy ny f no` control bits.
+fn alu(x: i16, y: i16, ctrl: u16) -> i16 {
+    let bit = |n: u16| ctrl & (1 << n) != 0;
+
+    let x = if bit(5) { 0 } else { x };
+    let x = if bit(4) { !x } else { x };
+    let y = if bit(3) { 0 } else { y };
+    let y = if bit(2) { !y } else { y };
+    let out = if bit(1) { x.wrapping_add(y) } else { x & y };
+
+    if bit(0) {
+        !out
+    } else {
+        out
+    }
+}
+
+fn jumps(jump: u16, val: i16) -> bool {
+    (jump & 0b100 != 0 && val < 0)
+        || (jump & 0b010 != 0 && val == 0)
+        || (jump & 0b001 != 0 && val > 0)
+}
+
+/// A Hack CPU running assembled machine code.
+pub struct Cpu {
+    rom: Vec<u16>,
+    ram: Vec<i16>,
+    pc: usize,
+    a: i16,
+    d: i16,
+}
+
+impl Cpu {
+    pub fn new(program: &[Instruction]) -> Self {
+        let rom = assemble::assemble(program)
+            .iter()
+            .map(|word| u16::from_str_radix(word, 2).unwrap())
+            .collect();
+
+        Cpu::load(rom)
+    }
+
+    pub fn load(rom: Vec<u16>) -> Self {
+        Cpu {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+            a: 0,
+            d: 0,
+        }
+    }
+
+    pub fn ram(&self) -> &[i16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [i16] {
+        &mut self.ram
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Determines if the CPU is stuck in an `@x; 0;JMP` loop at `x`.
+    pub fn halted(&self) -> bool {
+        if self.pc >= self.rom.len() {
+            return true;
+        }
+
+        self.rom[self.pc] as usize == self.pc && self.rom.get(self.pc + 1) == Some(&JMP_NO_COND)
+    }
+
+    pub fn step(&mut self) {
+        let word = self.rom[self.pc];
+
+        if word & 0x8000 == 0 {
+            self.a = word as i16;
+            self.pc += 1;
+            return;
+        }
+
+        let addr = self.a as u16 as usize;
+        let y = if word & 0x1000 != 0 {
+            self.ram[addr]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (word >> 6) & 0x3f);
+
+        if word & 0b001_000 != 0 {
+            self.ram[addr] = out;
+        }
+        if word & 0b010_000 != 0 {
+            self.d = out;
+        }
+        if word & 0b100_000 != 0 {
+            self.a = out;
+        }
+
+        self.pc = if jumps(word & 0b111, out) {
+            addr
+        } else {
+            self.pc + 1
+        };
+    }
+
+    /// Runs until the program halts, giving up after `max_steps` instructions.
+    pub fn run(&mut self, max_steps: usize) -> bool {
+        for _ in 0..max_steps {
+            if self.halted() {
+                return true;
+            }
+            self.step();
+        }
+
+        self.halted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(dest: Option<&str>, comp: &str, jump: Option<&str>) -> Instruction {
+        Instruction::C {
+            dest: dest.map(|x| x.to_string()),
+            comp: comp.to_string(),
+            jump: jump.map(|x| x.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_add_registers() {
+        let program = [
+            Instruction::AConst(2),
+            c(Some("D"), "A", None),
+            Instruction::AConst(3),
+            c(Some("D"), "D+A", None),
+            Instruction::AVar("R0".to_string()),
+            c(Some("M"), "D", None),
+        ];
+
+        let mut cpu = Cpu::new(&program);
+        assert!(cpu.run(100));
+        assert_eq!(5, cpu.ram()[0]);
+    }
+
+    #[test]
+    fn test_variables_and_labels() {
+        let program = [
+            Instruction::AVar("x".to_string()),
+            c(Some("M"), "-1", None),
+            Instruction::AVar("y".to_string()),
+            c(Some("M"), "1", None),
+            Instruction::L("END".to_string()),
+            Instruction::AVar("END".to_string()),
+            c(None, "0", Some("JMP")),
+        ];
+
+        let mut cpu = Cpu::new(&program);
+        assert!(cpu.run(100));
+        assert_eq!(4, cpu.pc());
+        assert_eq!(-1, cpu.ram()[16]);
+        assert_eq!(1, cpu.ram()[17]);
+    }
+
+    #[test]
+    fn test_alu() {
+        let program = [
+            Instruction::AConst(12),
+            c(Some("D"), "A", None),
+            Instruction::AConst(5),
+            c(Some("D"), "D-A", None),
+            c(Some("D"), "!D", None),
+            Instruction::AConst(3),
+            c(Some("D"), "D|A", None),
+            Instruction::AConst(100),
+            c(Some("AM"), "D+A", None),
+            c(Some("M"), "-1", None),
+        ];
+
+        let mut cpu = Cpu::new(&program);
+        assert!(cpu.run(100));
+        assert_eq!(95, cpu.ram()[100]);
+        assert_eq!(-1, cpu.ram()[95]);
+    }
+}
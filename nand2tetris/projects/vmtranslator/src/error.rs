@@ -0,0 +1,35 @@
+use crate::ir::Position;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VmError {
+    pub pos: Option<Position>,
+    pub msg: String,
+}
+
+impl VmError {
+    pub fn new(msg: &str) -> Self {
+        VmError {
+            pos: None,
+            msg: msg.to_string(),
+        }
+    }
+
+    pub fn at(pos: &Position, msg: &str) -> Self {
+        VmError {
+            pos: Some(pos.clone()),
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.pos {
+            Some(pos) => write!(f, "{}: {}", pos, self.msg),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
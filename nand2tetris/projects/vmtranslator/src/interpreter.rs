@@ -0,0 +1,316 @@
+use crate::command::{ArithmeticOp, Command, Segment};
+use crate::emulator::RAM_SIZE;
+use std::collections::HashMap;
+
+const SP: usize = 0;
+const LCL: usize = 1;
+const ARG: usize = 2;
+const THIS: usize = 3;
+const THAT: usize = 4;
+const TEMP: usize = 5;
+
+fn bool_val(val: bool) -> i16 {
+    if val {
+        -1
+    } else {
+        0
+    }
+}
+
+fn scoped(func: &Option<String>, label: &str) -> String {
+    match func {
+        Some(func) => format!("{}${}", func, label),
+        None => label.to_string(),
+    }
+}
+
+/// A reference VM that executes commands directly against a Hack-shaped RAM.
+pub struct Vm<'a> {
+    program: &'a [Command],
+    functions: HashMap<String, usize>,
+    jumps: HashMap<usize, usize>,
+    statics: HashMap<(String, i16), usize>,
+    ram: Vec<i16>,
+    pc: usize,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a [Command]) -> Self {
+        let mut functions = HashMap::new();
+        let mut labels = HashMap::new();
+        let mut statics = HashMap::new();
+        let mut cur_func = None;
+
+        for (idx, cmd) in program.iter().enumerate() {
+            match cmd {
+                Command::Function(func, _) => {
+                    functions.insert(func.to_string(), idx);
+                    cur_func = Some(func.to_string());
+                }
+                Command::Label(label) => {
+                    labels.insert(scoped(&cur_func, label), idx);
+                }
+                Command::Push(Segment::Static(file, offset))
+                | Command::Pop(Segment::Static(file, offset)) => {
+                    let next_addr = 16 + statics.len();
+                    statics
+                        .entry((file.to_string(), *offset))
+                        .or_insert(next_addr);
+                }
+                _ => {}
+            }
+        }
+
+        let mut jumps = HashMap::new();
+        cur_func = None;
+
+        for (idx, cmd) in program.iter().enumerate() {
+            match cmd {
+                Command::Function(func, _) => cur_func = Some(func.to_string()),
+                Command::Goto(label) | Command::IfGoto(label) => {
+                    jumps.insert(idx, labels[&scoped(&cur_func, label)]);
+                }
+                _ => {}
+            }
+        }
+
+        Vm {
+            program,
+            functions,
+            jumps,
+            statics,
+            ram: vec![0; RAM_SIZE],
+            pc: 0,
+        }
+    }
+
+    pub fn ram(&self) -> &[i16] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [i16] {
+        &mut self.ram
+    }
+
+    fn reg(&self, reg: usize) -> usize {
+        self.ram[reg] as u16 as usize
+    }
+
+    fn push_val(&mut self, val: i16) {
+        let sp = self.reg(SP);
+        self.ram[sp] = val;
+        self.ram[SP] += 1;
+    }
+
+    fn pop_val(&mut self) -> i16 {
+        self.ram[SP] -= 1;
+        self.ram[self.reg(SP)]
+    }
+
+    fn addr(&self, segment: &Segment) -> usize {
+        match segment {
+            Segment::Argument(offset) => self.reg(ARG) + *offset as usize,
+            Segment::Local(offset) => self.reg(LCL) + *offset as usize,
+            Segment::This(offset) => self.reg(THIS) + *offset as usize,
+            Segment::That(offset) => self.reg(THAT) + *offset as usize,
+            Segment::Temp(offset) => TEMP + *offset as usize,
+            Segment::Pointer(offset) => THIS + *offset as usize,
+            Segment::Static(file, offset) => self.statics[&(file.to_string(), *offset)],
+            Segment::Named(name) => match name.as_str() {
+                "LCL" => LCL,
+                "ARG" => ARG,
+                "THIS" => THIS,
+                "THAT" => THAT,
+                _ => panic!("unknown register {}", name),
+            },
+            Segment::Constant(_) | Segment::NamedPtr(_) => {
+                panic!("{:?} has no address", segment)
+            }
+        }
+    }
+
+    fn arithmetic(&mut self, op: &ArithmeticOp) {
+        let val = match op {
+            ArithmeticOp::Negate => self.pop_val().wrapping_neg(),
+            ArithmeticOp::Not => !self.pop_val(),
+            _ => {
+                let y = self.pop_val();
+                let x = self.pop_val();
+                match op {
+                    ArithmeticOp::Add => x.wrapping_add(y),
+                    ArithmeticOp::Subtract => x.wrapping_sub(y),
+                    ArithmeticOp::Equal => bool_val(x == y),
+                    ArithmeticOp::GreaterThan => bool_val(x > y),
+                    ArithmeticOp::LessThan => bool_val(x < y),
+                    ArithmeticOp::And => x & y,
+                    ArithmeticOp::Or => x | y,
+                    ArithmeticOp::Multiply => x.wrapping_mul(y),
+                    ArithmeticOp::Divide if y == 0 => 0,
+                    ArithmeticOp::Divide => x.wrapping_div(y),
+                    ArithmeticOp::Modulo if y == 0 => 0,
+                    ArithmeticOp::Modulo => x.wrapping_rem(y),
+                    ArithmeticOp::ShiftLeft if y <= 0 => x,
+                    ArithmeticOp::ShiftLeft if y >= 16 => 0,
+                    ArithmeticOp::ShiftLeft => x << y,
+                    ArithmeticOp::ShiftRight if y <= 0 => x,
+                    ArithmeticOp::ShiftRight if y >= 16 => 0,
+                    ArithmeticOp::ShiftRight => ((x as u16) >> y) as i16,
+                    ArithmeticOp::Negate | ArithmeticOp::Not => unreachable!(),
+                }
+            }
+        };
+
+        self.push_val(val);
+    }
+
+    fn call(&mut self, func: &str, arg_cnt: usize) {
+        self.push_val((self.pc + 1) as i16);
+        for reg in [LCL, ARG, THIS, THAT].iter() {
+            self.push_val(self.ram[*reg]);
+        }
+
+        self.ram[ARG] = self.ram[SP] - 5 - arg_cnt as i16;
+        self.ram[LCL] = self.ram[SP];
+        self.pc = self.functions[func];
+    }
+
+    /// Moves the arguments and our caller's saved frame down to `ARG`, then
+    /// enters `func` as if our caller had called it.
+    fn tail_call(&mut self, func: &str, arg_cnt: usize) {
+        let frame = self.reg(LCL);
+        let arg = self.reg(ARG);
+        let sp = self.reg(SP);
+
+        let mut words = self.ram[sp - arg_cnt..sp].to_vec();
+        words.extend_from_slice(&self.ram[frame - 5..frame]);
+        self.ram[arg..arg + words.len()].copy_from_slice(&words);
+
+        self.ram[LCL] = (arg + words.len()) as i16;
+        self.ram[SP] = self.ram[LCL];
+        self.pc = self.functions[func];
+    }
+
+    fn ret(&mut self) {
+        let frame = self.reg(LCL);
+        let ret_addr = self.ram[frame - 5];
+
+        let ret_val = self.pop_val();
+        let arg = self.reg(ARG);
+        self.ram[arg] = ret_val;
+        self.ram[SP] = self.ram[ARG] + 1;
+
+        self.ram[THAT] = self.ram[frame - 1];
+        self.ram[THIS] = self.ram[frame - 2];
+        self.ram[ARG] = self.ram[frame - 3];
+        self.ram[LCL] = self.ram[frame - 4];
+
+        self.pc = ret_addr as usize;
+    }
+
+    /// Determines if execution ended or is stuck in a `label x; goto x` loop.
+    pub fn halted(&self) -> bool {
+        match self.program.get(self.pc) {
+            Some(Command::Goto(_)) => self.jumps[&self.pc] + 1 == self.pc,
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    pub fn step(&mut self) {
+        let cmd = &self.program[self.pc];
+        let mut next_pc = self.pc + 1;
+
+        match cmd {
+            Command::Arithmetic(op) => self.arithmetic(op),
+            Command::Push(Segment::Constant(val)) => self.push_val(*val),
+            Command::Push(segment) => self.push_val(self.ram[self.addr(segment)]),
+            Command::Pop(segment) => {
+                let addr = self.addr(segment);
+                self.ram[addr] = self.pop_val();
+            }
+            Command::Label(_) => {}
+            Command::Goto(_) => next_pc = self.jumps[&self.pc],
+            Command::IfGoto(_) => {
+                if self.pop_val() != 0 {
+                    next_pc = self.jumps[&self.pc];
+                }
+            }
+            Command::Function(_, local_cnt) => {
+                for _ in 0..*local_cnt {
+                    self.push_val(0);
+                }
+            }
+            Command::Call(func, arg_cnt) => {
+                self.call(func, *arg_cnt);
+                return;
+            }
+            Command::TailCall(func, arg_cnt) => {
+                self.tail_call(func, *arg_cnt);
+                return;
+            }
+            Command::Return => {
+                self.ret();
+                return;
+            }
+        }
+
+        self.pc = next_pc;
+    }
+
+    /// Runs until the program halts, giving up after `max_steps` commands.
+    pub fn run(&mut self, max_steps: usize) -> bool {
+        for _ in 0..max_steps {
+            if self.halted() {
+                return true;
+            }
+            self.step();
+        }
+
+        self.halted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let program = [
+            Command::Push(Segment::Constant(7)),
+            Command::Push(Segment::Constant(8)),
+            Command::Arithmetic(ArithmeticOp::Add),
+            Command::Push(Segment::Constant(20)),
+            Command::Arithmetic(ArithmeticOp::LessThan),
+        ];
+
+        let mut vm = Vm::new(&program);
+        vm.ram_mut()[SP] = 256;
+        assert!(vm.run(100));
+
+        assert_eq!(257, vm.ram()[SP]);
+        assert_eq!(-1, vm.ram()[256]);
+    }
+
+    #[test]
+    fn test_call_return() {
+        let program = [
+            Command::Push(Segment::Constant(3)),
+            Command::Call("Main.double".to_string(), 1),
+            Command::Label("END".to_string()),
+            Command::Goto("END".to_string()),
+            Command::Function("Main.double".to_string(), 0),
+            Command::Push(Segment::Argument(0)),
+            Command::Push(Segment::Argument(0)),
+            Command::Arithmetic(ArithmeticOp::Add),
+            Command::Return,
+        ];
+
+        let mut vm = Vm::new(&program);
+        vm.ram_mut()[SP] = 256;
+        assert!(vm.run(100));
+
+        assert_eq!(257, vm.ram()[SP]);
+        assert_eq!(6, vm.ram()[256]);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::command::Command;
+use crate::error::VmError;
+use crate::parser::Parser;
+use std::fmt;
+use std::io::BufRead;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub file: String,
+    pub line: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// A command along with where it came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Located {
+    pub cmd: Command,
+    pub pos: Position,
+}
+
+/// The commands of a single `.vm` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Module {
+    /// The file stem, which also names the file's static segment.
+    pub name: String,
+    pub commands: Vec<Located>,
+}
+
+impl Module {
+    pub fn parse<T: BufRead>(reader: T, name: &str) -> Result<Self, Vec<VmError>> {
+        let file = format!("{}.vm", name);
+        let mut parser = Parser::new(reader.lines(), name.to_string());
+        let mut commands = vec![];
+        let mut errors = vec![];
+
+        while parser.has_more_lines() {
+            let pos = Position {
+                file: file.clone(),
+                line: parser.line_number(),
+            };
+
+            match (parser.command(), parser.error()) {
+                (Some(cmd), _) => commands.push(Located {
+                    cmd: cmd.clone(),
+                    pos,
+                }),
+                (None, Some(err)) => errors.push(VmError::at(&pos, err)),
+                (None, None) => {}
+            }
+            parser.advance();
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Module {
+            name: name.to_string(),
+            commands,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    pub modules: Vec<Module>,
+}
+
+impl Program {
+    /// Parses named inputs, one module each, reporting every module's errors.
+    pub fn parse<N, R, I>(inputs: I) -> Result<Self, Vec<VmError>>
+    where
+        N: AsRef<str>,
+        R: BufRead,
+        I: IntoIterator<Item = (N, R)>,
+    {
+        let mut program = Program::default();
+        let mut errors = vec![];
+
+        for (name, reader) in inputs {
+            match Module::parse(reader, name.as_ref()) {
+                Ok(module) => program.modules.push(module),
+                Err(mut errs) => errors.append(&mut errs),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &Located> {
+        self.modules
+            .iter()
+            .flat_map(|module| module.commands.iter())
+    }
+
+    pub fn defines(&self, func: &str) -> bool {
+        self.commands()
+            .any(|located| matches!(&located.cmd, Command::Function(name, _) if name == func))
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for module in &self.modules {
+            writeln!(f, "// {}.vm", module.name)?;
+            for located in &module.commands {
+                writeln!(f, "{}", located.cmd)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Segment;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_parse_module() {
+        let input = "function Main.main 0\n\n    push static 1\nreturn\n";
+        let module = Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap();
+
+        let expected = vec![
+            Located {
+                cmd: Command::Function("Main.main".to_string(), 0),
+                pos: Position {
+                    file: "Main.vm".to_string(),
+                    line: 1,
+                },
+            },
+            Located {
+                cmd: Command::Push(Segment::Static("Main".to_string(), 1)),
+                pos: Position {
+                    file: "Main.vm".to_string(),
+                    line: 3,
+                },
+            },
+            Located {
+                cmd: Command::Return,
+                pos: Position {
+                    file: "Main.vm".to_string(),
+                    line: 4,
+                },
+            },
+        ];
+
+        assert_eq!(expected, module.commands);
+    }
+
+    #[test]
+    fn test_display_program() {
+        let input = "push constant 7\npush static 2\nadd\n";
+        let program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+
+        assert_eq!(
+            "// Main.vm\npush constant 7\npush static 2\nadd\n",
+            program.to_string()
+        );
+    }
+}
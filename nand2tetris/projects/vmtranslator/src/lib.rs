@@ -1,4 +1,15 @@
+pub mod analyze;
+pub mod callgraph;
 pub mod code;
 pub mod command;
+pub mod emulator;
+pub mod error;
 pub mod instruct;
+pub mod interpreter;
+pub mod ir;
 pub mod parser;
+pub mod pass;
+pub mod sourcemap;
+pub mod translate;
+
+pub use translate::translate;
@@ -1,69 +1,351 @@
+use assembler::assemble;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
+use vmtranslator::analyze;
 use vmtranslator::code;
-use vmtranslator::parser;
+use vmtranslator::error::VmError;
+use vmtranslator::ir::Program;
+use vmtranslator::translate;
 
 #[derive(StructOpt)]
 struct Args {
+    /// A `.vm` file, a directory of them, or `-` for stdin.
     #[structopt(parse(from_os_str))]
-    input: PathBuf,
+    input: Option<PathBuf>,
 
+    /// Where to write the output, `-` for stdout. Defaults to the input with
+    /// the output extension, or stdout when reading stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Module name for input read from stdin, which names its statics.
+    #[structopt(long, default_value = "Main")]
+    name: String,
+
+    #[structopt(subcommand)]
+    mode: Option<Mode>,
+
+    #[structopt(flatten)]
+    bootstrap: BootstrapArgs,
+
+    /// Allow the extended `mul`, `div`, `mod`, `shl` and `shr` commands.
+    #[structopt(long)]
+    extended: bool,
+
+    /// Use single-subtraction `gt`/`lt`, which are wrong when `x - y` overflows.
+    #[structopt(name = "fast-compare", long)]
+    fast_compare: bool,
+
+    /// Precede each expansion with a comment naming its VM source line.
+    #[structopt(long)]
+    annotate: bool,
+
+    /// Guard against stack overflow and underflow, `that` accesses outside
+    /// memory and unbalanced returns, halting with an error code in RAM[2047].
+    #[structopt(long)]
+    checked: bool,
+
+    /// The highest value SP may take in checked code, above the stack base
+    /// and at most 2047.
+    #[structopt(name = "stack-limit", long, default_value = "2047", parse(try_from_str = parse_stack_limit))]
+    stack_limit: i16,
+
+    /// Output format: asm or hack.
+    #[structopt(long, default_value = "asm")]
+    emit: code::Emit,
+
+    /// Also write a `.map` file relating each ROM address to its VM command.
+    #[structopt(name = "source-map", long)]
+    source_map: bool,
+
+    /// Inline leaf functions with at most this many commands (0 disables).
+    #[structopt(name = "inline-threshold", long, default_value = "0")]
+    inline_threshold: usize,
+
+    /// Skip a pass (may be repeated).
+    #[structopt(name = "disable-pass", long, number_of_values = 1)]
+    disable_pass: Vec<String>,
+
+    /// Print the VM program to stderr after a pass (may be repeated).
+    #[structopt(name = "dump-after", long, number_of_values = 1)]
+    dump_after: Vec<String>,
+
+    /// Print to stderr what each pass did, including where each file's
+    /// statics and the scratch variables live.
+    #[structopt(short, long)]
+    verbose: bool,
+}
+
+#[derive(StructOpt)]
+enum Mode {
+    /// Report the call graph, recursion and worst-case stack usage.
+    Analyze {
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        #[structopt(flatten)]
+        bootstrap: BootstrapArgs,
+    },
+}
+
+enum BootstrapMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for BootstrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("expected auto, always or never, found `{}`", s)),
+        }
+    }
+}
+
+#[derive(StructOpt)]
+struct BootstrapArgs {
+    /// Whether to emit bootstrap code: auto (when the entry function is
+    /// defined), always or never.
+    #[structopt(long, default_value = "auto")]
+    bootstrap: BootstrapMode,
+
+    /// Same as `--bootstrap never`.
     #[structopt(name = "no-sys-init", long)]
     no_sys_init: bool,
+
+    /// Initial value of SP, from 0 to 32767.
+    #[structopt(name = "stack-base", long, default_value = "256", parse(try_from_str = parse_stack_base))]
+    stack_base: i16,
+
+    /// Function called by the bootstrap code.
+    #[structopt(long, default_value = "Sys.init")]
+    entry: String,
+
+    /// Set LCL, ARG, THIS and THAT to -1, -2, -3 and -4 before the entry call.
+    #[structopt(long)]
+    sentinels: bool,
 }
 
-fn main() -> std::io::Result<()> {
-    let args = Args::from_args();
+fn parse_stack_base(s: &str) -> Result<i16, String> {
+    match s.parse::<i16>() {
+        Ok(base) if base >= 0 => Ok(base),
+        _ => Err(format!(
+            "expected an address from 0 to 32767, found `{}`",
+            s
+        )),
+    }
+}
 
-    let input_path = args.input;
+fn parse_stack_limit(s: &str) -> Result<i16, String> {
+    match s.parse::<i16>() {
+        Ok(limit) if (0..=code::ERROR_ADDR).contains(&limit) => Ok(limit),
+        _ => Err(format!(
+            "expected an address from 0 to {}, found `{}`",
+            code::ERROR_ADDR,
+            s
+        )),
+    }
+}
+
+impl BootstrapArgs {
+    /// Whether the bootstrap depends on the entry being defined, and the
+    /// bootstrap itself.
+    fn options(&self) -> (bool, Option<code::Bootstrap>) {
+        let bootstrap = code::Bootstrap {
+            stack_base: self.stack_base,
+            entry: self.entry.to_string(),
+            sentinels: self.sentinels,
+        };
 
-    let mut output_path = input_path.clone();
-    if output_path.is_dir() {
-        let out_clone = output_path.clone();
-        let file_name = out_clone.file_name().unwrap();
-        output_path.push(file_name);
+        match self.bootstrap {
+            _ if self.no_sys_init => (false, None),
+            BootstrapMode::Auto => (true, Some(bootstrap)),
+            BootstrapMode::Always => (false, Some(bootstrap)),
+            BootstrapMode::Never => (false, None),
+        }
     }
-    output_path.set_extension("asm");
+}
 
-    let out_file = File::create(output_path)?;
+fn report(errors: &[VmError]) -> std::io::Error {
+    for error in errors {
+        eprintln!("error: {}", error);
+    }
+
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{} error(s) in VM program", errors.len()),
+    )
+}
 
-    let buf_writer = BufWriter::new(out_file);
-    let mut writer = code::CodeWriter::new(buf_writer, args.no_sys_init);
+fn read_error(path: &Path, err: std::io::Error) -> Vec<VmError> {
+    vec![VmError::new(&format!(
+        "cannot read {}: {}",
+        path.display(),
+        err
+    ))]
+}
 
+fn load_program(input_path: &Path) -> Result<Program, Vec<VmError>> {
     let files = if input_path.is_dir() {
-        input_path
+        let mut files = input_path
             .read_dir()
-            .unwrap()
-            .filter(|x| x.is_ok())
-            .map(|x| x.unwrap())
+            .map_err(|err| read_error(input_path, err))?
+            .filter_map(|x| x.ok())
             .map(|x| x.path())
-            .filter(|x| x.extension().unwrap().to_str().unwrap() == "vm")
-            .collect()
+            .filter(|x| x.is_file() && x.extension().is_some_and(|ext| ext == "vm"))
+            .collect::<Vec<PathBuf>>();
+
+        // Directory order differs between machines, so sort for stable output.
+        files.sort();
+        files
     } else {
-        vec![input_path]
+        vec![input_path.to_path_buf()]
     };
 
-    for file in files {
-        writer.on_new_file();
-        let in_file = File::open(file.clone())?;
-        let reader = BufReader::new(in_file);
-        let mut parser = parser::Parser::new(
-            reader.lines(),
-            file.file_stem().unwrap().to_str().unwrap().to_string(),
-        );
+    let mut inputs = vec![];
+    for file in &files {
+        // The stem names the module's statics, so it must be usable text.
+        let name = match file.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name,
+            None => {
+                return Err(vec![VmError::new(&format!(
+                    "cannot name a module after {}",
+                    file.display()
+                ))])
+            }
+        };
+        let reader = BufReader::new(File::open(file).map_err(|err| read_error(file, err))?);
+        inputs.push((name, reader));
+    }
 
-        while parser.has_more_lines() {
-            let cmd = parser.command().as_ref().unwrap();
+    Program::parse(inputs)
+}
 
-            writer.write(cmd)?;
+fn main() -> std::io::Result<()> {
+    let args = Args::from_args();
+
+    let input_path = match (args.mode, args.input) {
+        (Some(Mode::Analyze { input, bootstrap }), _) => {
+            let program = load_program(&input).map_err(|errors| report(&errors))?;
+            let report =
+                analyze::analyze(&program, &bootstrap.entry, bootstrap.stack_base as usize);
+            print!("{}", report);
+            return Ok(());
+        }
+        (None, Some(input)) => input,
+        (None, None) => {
+            Args::clap().print_help().map_err(std::io::Error::other)?;
+            println!();
+            return Ok(());
+        }
+    };
 
-            parser.advance();
+    let stdin = input_path == Path::new("-");
+    let output_path = match args.output {
+        Some(path) => path,
+        None if stdin => PathBuf::from("-"),
+        None => {
+            let mut output_path = input_path.clone();
+            if output_path.is_dir() {
+                // `..` has no name of its own, but the directory it means does.
+                let dir = output_path.canonicalize()?;
+                match dir.file_name() {
+                    Some(file_name) => output_path.push(file_name),
+                    None => {
+                        return Err(report(&[VmError::new(&format!(
+                            "cannot name the output after {}, so pass --output",
+                            input_path.display()
+                        ))]))
+                    }
+                }
+            }
+            output_path.set_extension(args.emit.extension());
+            output_path
         }
+    };
+    let stdout = output_path == Path::new("-");
+
+    if args.source_map && stdout {
+        return Err(report(&[VmError::new(
+            "--source-map needs an output file, not stdout",
+        )]));
+    }
+
+    let mut program = if stdin {
+        Program::parse(vec![(&args.name, std::io::stdin().lock())])
+    } else {
+        load_program(&input_path)
     }
+    .map_err(|errors| report(&errors))?;
 
-    writer.close()?;
+    let (auto_bootstrap, bootstrap) = args.bootstrap.options();
+    let options = translate::Options {
+        code: code::Options {
+            bootstrap,
+            fast_compare: args.fast_compare,
+            annotate: args.annotate,
+            emit: args.emit,
+            checks: if args.checked {
+                Some(code::Checks {
+                    stack_limit: args.stack_limit,
+                })
+            } else {
+                None
+            },
+        },
+        auto_bootstrap,
+        entry: args.bootstrap.entry.to_string(),
+        inline_threshold: args.inline_threshold,
+        extended: args.extended,
+        disabled_passes: args.disable_pass,
+        dump_after: args.dump_after,
+        // A directory is a whole program, a single file may be linked later.
+        check_calls: input_path.is_dir(),
+        report_statics: args.verbose,
+    };
+
+    let translation = translate::translate_program(&mut program, &options, &mut std::io::stderr())
+        .map_err(|errors| report(&errors))?;
+    if args.verbose {
+        for note in &translation.notes {
+            eprintln!("{}", note);
+        }
+    }
+
+    let lines = match args.emit {
+        code::Emit::Asm => translation
+            .instructions
+            .iter()
+            .map(|x| x.to_string())
+            .collect(),
+        code::Emit::Hack => assemble::assemble(&translation.instructions),
+    };
+
+    let out: Box<dyn Write> = if stdout {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(File::create(&output_path)?)
+    };
+    let mut writer = BufWriter::new(out);
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    if args.source_map {
+        std::fs::write(
+            output_path.with_extension("map"),
+            translation.source_map.to_string(),
+        )?;
+    }
 
     Ok(())
 }
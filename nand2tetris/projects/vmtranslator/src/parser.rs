@@ -21,21 +21,32 @@ fn strip_trailing_comment(line: &str) -> String {
     }
 }
 
-fn parse_seg(seg_str: &str, val: i16, file_name: &str) -> Segment {
-    match seg_str {
+fn parse_seg(seg_str: &str, val: i16, file_name: &str) -> Result<Segment, String> {
+    // Constants and offsets end up in A-instructions, which hold 15 bits.
+    if val < 0 {
+        return Err(format!(
+            "expected a number from 0 to 32767, found `{}`",
+            val
+        ));
+    }
+
+    let seg = match seg_str {
         "argument" => Segment::Argument(val),
         "local" => Segment::Local(val),
         "static" => Segment::Static(file_name.to_string(), val),
+        "constant" => Segment::Constant(val),
         "this" => Segment::This(val),
         "that" => Segment::That(val),
         "temp" => Segment::Temp(val),
         "pointer" => Segment::Pointer(val),
-        _ => Segment::Constant(val),
-    }
+        _ => return Err(format!("unknown segment `{}`", seg_str)),
+    };
+
+    Ok(seg)
 }
 
-fn parse_arithmetic_op(line: &str) -> ArithmeticOp {
-    match line {
+fn parse_arithmetic_op(line: &str) -> Result<ArithmeticOp, String> {
+    let op = match line {
         "add" => ArithmeticOp::Add,
         "sub" => ArithmeticOp::Subtract,
         "neg" => ArithmeticOp::Negate,
@@ -45,31 +56,64 @@ fn parse_arithmetic_op(line: &str) -> ArithmeticOp {
         "and" => ArithmeticOp::And,
         "or" => ArithmeticOp::Or,
         "not" => ArithmeticOp::Not,
-        _ => ArithmeticOp::Equal,
-    }
+        "mul" => ArithmeticOp::Multiply,
+        "div" => ArithmeticOp::Divide,
+        "mod" => ArithmeticOp::Modulo,
+        "shl" => ArithmeticOp::ShiftLeft,
+        "shr" => ArithmeticOp::ShiftRight,
+        _ => return Err(format!("unknown command `{}`", line)),
+    };
+
+    Ok(op)
 }
 
-fn parse_cmd(clean_line: &str, file_name: &str) -> Command {
+fn operand<'a>(tokens: &[&'a str], idx: usize) -> Result<&'a str, String> {
+    tokens
+        .get(idx)
+        .copied()
+        .ok_or_else(|| format!("`{}` expects {} operand(s)", tokens[0], idx))
+}
+
+fn number<N: std::str::FromStr>(tokens: &[&str], idx: usize) -> Result<N, String> {
+    let token = operand(tokens, idx)?;
+    token
+        .parse()
+        .map_err(|_| format!("expected a number, found `{}`", token))
+}
+
+fn parse_cmd(clean_line: &str, file_name: &str) -> Result<Command, String> {
     let tokens = clean_line.split_whitespace().collect::<Vec<&str>>();
 
-    match tokens[0] {
-        "push" => Command::Push(parse_seg(tokens[1], tokens[2].parse().unwrap(), file_name)),
-        "pop" => Command::Pop(parse_seg(tokens[1], tokens[2].parse().unwrap(), file_name)),
-        "label" => Command::Label(tokens[1].to_string()),
-        "goto" => Command::Goto(tokens[1].to_string()),
-        "if-goto" => Command::IfGoto(tokens[1].to_string()),
-        "call" => Command::Call(tokens[1].to_string(), tokens[2].parse().unwrap()),
-        "function" => Command::Function(tokens[1].to_string(), tokens[2].parse().unwrap()),
+    let cmd = match tokens[0] {
+        "push" => Command::Push(parse_seg(
+            operand(&tokens, 1)?,
+            number(&tokens, 2)?,
+            file_name,
+        )?),
+        "pop" => Command::Pop(parse_seg(
+            operand(&tokens, 1)?,
+            number(&tokens, 2)?,
+            file_name,
+        )?),
+        "label" => Command::Label(operand(&tokens, 1)?.to_string()),
+        "goto" => Command::Goto(operand(&tokens, 1)?.to_string()),
+        "if-goto" => Command::IfGoto(operand(&tokens, 1)?.to_string()),
+        "call" => Command::Call(operand(&tokens, 1)?.to_string(), number(&tokens, 2)?),
+        "function" => Command::Function(operand(&tokens, 1)?.to_string(), number(&tokens, 2)?),
         "return" => Command::Return,
-        _ => Command::Arithmetic(parse_arithmetic_op(clean_line)),
-    }
+        _ => Command::Arithmetic(parse_arithmetic_op(clean_line)?),
+    };
+
+    Ok(cmd)
 }
 
 pub struct Parser<T: BufRead> {
     lines: Lines<T>,
     cur_cmd: Option<Command>,
+    cur_error: Option<String>,
     more_lines: bool,
     file_name: String,
+    line_no: usize,
 }
 
 impl<T: BufRead> Parser<T> {
@@ -77,8 +121,10 @@ impl<T: BufRead> Parser<T> {
         let mut parser = Parser {
             lines,
             cur_cmd: None,
+            cur_error: None,
             more_lines: true,
             file_name: file,
+            line_no: 0,
         };
 
         parser.advance();
@@ -94,29 +140,49 @@ impl<T: BufRead> Parser<T> {
         let mut invalid = true;
         let mut curr_line = String::new();
 
+        self.cur_cmd = None;
+        self.cur_error = None;
+
         while invalid {
             match self.lines.next() {
-                Some(line) => {
-                    curr_line = line.unwrap();
-                    invalid = superficial(&curr_line);
+                Some(Ok(line)) => {
+                    curr_line = line;
+                    self.line_no += 1;
+                    invalid = superficial(curr_line.trim());
+                }
+                Some(Err(err)) => {
+                    self.line_no += 1;
+                    self.cur_error = Some(format!("cannot read line: {}", err));
+                    return;
                 }
                 None => {
                     self.more_lines = false;
-                    self.cur_cmd = None;
                     return;
                 }
             };
         }
 
-        let clean_line = strip_trailing_comment(&curr_line);
-        let temp_cmd = parse_cmd(&clean_line, &self.file_name);
-
-        self.cur_cmd = Some(temp_cmd);
+        let clean_line = strip_trailing_comment(curr_line.trim());
+        match parse_cmd(&clean_line, &self.file_name) {
+            Ok(cmd) => self.cur_cmd = Some(cmd),
+            Err(err) => self.cur_error = Some(err),
+        }
     }
 
+    /// The current command, or `None` when its line has an error.
     pub fn command(&self) -> &Option<Command> {
         &self.cur_cmd
     }
+
+    /// Why the current line could not be parsed.
+    pub fn error(&self) -> Option<&str> {
+        self.cur_error.as_deref()
+    }
+
+    /// The 1-based source line of the current command.
+    pub fn line_number(&self) -> usize {
+        self.line_no
+    }
 }
 
 #[cfg(test)]
@@ -176,6 +242,26 @@ mod tests {
                 input_str: "not".to_string(),
                 expected: Command::Arithmetic(ArithmeticOp::Not),
             },
+            TestCase {
+                input_str: "mul".to_string(),
+                expected: Command::Arithmetic(ArithmeticOp::Multiply),
+            },
+            TestCase {
+                input_str: "div".to_string(),
+                expected: Command::Arithmetic(ArithmeticOp::Divide),
+            },
+            TestCase {
+                input_str: "mod".to_string(),
+                expected: Command::Arithmetic(ArithmeticOp::Modulo),
+            },
+            TestCase {
+                input_str: "shl".to_string(),
+                expected: Command::Arithmetic(ArithmeticOp::ShiftLeft),
+            },
+            TestCase {
+                input_str: "shr".to_string(),
+                expected: Command::Arithmetic(ArithmeticOp::ShiftRight),
+            },
         ];
 
         test_iter(&test_cases);
@@ -204,4 +290,49 @@ mod tests {
 
         test_iter(&test_cases);
     }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("push local", "`push` expects 2 operand(s)"),
+            ("push nowhere 1", "unknown segment `nowhere`"),
+            ("call Main.f x", "expected a number, found `x`"),
+            (
+                "push constant -5",
+                "expected a number from 0 to 32767, found `-5`",
+            ),
+            (
+                "pop that -1",
+                "expected a number from 0 to 32767, found `-1`",
+            ),
+            ("mull", "unknown command `mull`"),
+        ];
+
+        for (input, expected) in cases.iter() {
+            let reader = BufReader::new(input.as_bytes());
+            let parser = Parser::new(reader.lines(), "test".to_string());
+            assert_eq!(&None, parser.command());
+            assert_eq!(Some(*expected), parser.error());
+        }
+    }
+
+    #[test]
+    fn test_line_number() {
+        let input = "// comment\n\n  push constant 7\npush constant 8 // trailing\n";
+        let reader = BufReader::new(input.as_bytes());
+        let mut parser = Parser::new(reader.lines(), "test".to_string());
+
+        assert_eq!(3, parser.line_number());
+        assert_eq!(
+            &Command::Push(Segment::Constant(7)),
+            parser.command().as_ref().unwrap()
+        );
+
+        parser.advance();
+        assert_eq!(4, parser.line_number());
+        assert_eq!(
+            &Command::Push(Segment::Constant(8)),
+            parser.command().as_ref().unwrap()
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use crate::error::VmError;
+use crate::ir::Program;
+use std::io::Write;
+
+pub mod dfe;
+pub mod fold;
+pub mod inline;
+pub mod labels;
+pub mod link;
+pub mod standard;
+pub mod statics;
+pub mod tco;
+
+/// An analysis or transformation over a whole program.
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Runs the pass, returning notes about what it did.
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>>;
+}
+
+pub struct Pipeline {
+    passes: Vec<Box<dyn Pass>>,
+    disabled: Vec<String>,
+    dump_after: Vec<String>,
+}
+
+impl Pipeline {
+    pub fn new(passes: Vec<Box<dyn Pass>>) -> Self {
+        Pipeline {
+            passes,
+            disabled: vec![],
+            dump_after: vec![],
+        }
+    }
+
+    /// The default passes for a program starting at `entry` with its stack at
+    /// `stack_base`, inlining leaf functions of up to `inline_threshold`
+    /// commands. Calls to undefined functions are errors if `check_calls` is
+    /// set, and the static memory map is among the notes if `report_statics`
+    /// is.
+    pub fn standard(
+        entry: &str,
+        inline_threshold: usize,
+        stack_base: usize,
+        check_calls: bool,
+        report_statics: bool,
+    ) -> Self {
+        Pipeline::new(vec![
+            Box::new(standard::Standard),
+            Box::new(link::Link::new(check_calls)),
+            Box::new(labels::Labels),
+            Box::new(inline::Inline::new(inline_threshold)),
+            Box::new(dfe::Dfe::new(entry)),
+            Box::new(fold::Fold),
+            Box::new(tco::Tco),
+            Box::new(statics::Statics::new(stack_base, report_statics)),
+        ])
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), VmError> {
+        if self.pass_names().contains(&name) {
+            Ok(())
+        } else {
+            Err(VmError::new(&format!(
+                "unknown pass `{}`, expected one of: {}",
+                name,
+                self.pass_names().join(", ")
+            )))
+        }
+    }
+
+    pub fn disable(&mut self, name: &str) -> Result<(), VmError> {
+        self.check_name(name)?;
+        self.disabled.push(name.to_string());
+        Ok(())
+    }
+
+    pub fn dump_after(&mut self, name: &str) -> Result<(), VmError> {
+        self.check_name(name)?;
+        self.dump_after.push(name.to_string());
+        Ok(())
+    }
+
+    /// Runs every enabled pass in order, writing requested dumps to `dump`.
+    pub fn run<W: Write>(
+        &self,
+        program: &mut Program,
+        dump: &mut W,
+    ) -> Result<Vec<String>, Vec<VmError>> {
+        let mut notes = vec![];
+
+        for pass in &self.passes {
+            let name = pass.name().to_string();
+            if self.disabled.contains(&name) {
+                continue;
+            }
+
+            notes.append(&mut pass.run(program)?);
+
+            if self.dump_after.contains(&name) {
+                writeln!(dump, "// after {}\n{}", name, program)
+                    .map_err(|err| vec![VmError::new(&err.to_string())])?;
+            }
+        }
+
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn program(input: &str) -> Program {
+        Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_disable_pass() {
+        let mut prog = program("push constant 1\npush constant 2\nadd\n");
+        let mut pipeline = Pipeline::standard("Sys.init", 0, statics::STACK_BASE, false, false);
+        pipeline.disable("fold").unwrap();
+        pipeline.run(&mut prog, &mut vec![]).unwrap();
+
+        assert_eq!(3, prog.commands().count());
+    }
+
+    #[test]
+    fn test_dump_after() {
+        let mut prog = program("push constant 1\npush constant 2\nadd\n");
+        let mut pipeline = Pipeline::standard("Sys.init", 0, statics::STACK_BASE, false, false);
+        pipeline.dump_after("fold").unwrap();
+
+        let mut dump = vec![];
+        pipeline.run(&mut prog, &mut dump).unwrap();
+
+        assert_eq!(
+            "// after fold\n// Main.vm\npush constant 3\n\n",
+            String::from_utf8(dump).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unknown_pass() {
+        assert!(
+            Pipeline::standard("Sys.init", 0, statics::STACK_BASE, false, false)
+                .disable("nope")
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::callgraph::CallGraph;
+use crate::command::Command;
+use crate::error::VmError;
+use crate::ir::Program;
+use crate::pass::Pass;
+
+/// Removes functions that cannot be called starting from the entry function.
+pub struct Dfe {
+    entry: String,
+}
+
+impl Dfe {
+    pub fn new(entry: &str) -> Self {
+        Dfe {
+            entry: entry.to_string(),
+        }
+    }
+}
+
+impl Pass for Dfe {
+    fn name(&self) -> &'static str {
+        "dfe"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let graph = CallGraph::build(program);
+
+        // Without an entry point every function is a potential root.
+        if !graph.definitions.contains_key(&self.entry) {
+            return Ok(vec![]);
+        }
+
+        let reachable = graph.reachable(&[&self.entry]);
+        let mut removed = vec![];
+        let mut removed_cmds = 0;
+
+        for module in &mut program.modules {
+            let mut keep = true;
+
+            module.commands.retain(|located| {
+                if let Command::Function(func, _) = &located.cmd {
+                    keep = reachable.contains(func);
+                    if !keep {
+                        removed.push(func.to_string());
+                    }
+                }
+
+                if !keep {
+                    removed_cmds += 1;
+                }
+                keep
+            });
+        }
+
+        let mut notes = removed
+            .iter()
+            .map(|func| format!("dfe: removed unreachable function {}", func))
+            .collect::<Vec<String>>();
+
+        if !removed.is_empty() {
+            notes.push(format!(
+                "dfe: removed {} functions ({} commands)",
+                removed.len(),
+                removed_cmds
+            ));
+        }
+
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn program(files: &[(&str, &str)]) -> Program {
+        Program {
+            modules: files
+                .iter()
+                .map(|(name, input)| Module::parse(BufReader::new(input.as_bytes()), name).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_removes_unreachable() {
+        let mut prog = program(&[
+            ("Sys", "function Sys.init 0\ncall Main.main 0\nreturn\n"),
+            (
+                "Main",
+                "function Main.main 0\npush constant 0\nreturn\nfunction Main.unused 1\nreturn\n",
+            ),
+        ]);
+
+        let notes = Dfe::new("Sys.init").run(&mut prog).unwrap();
+
+        assert_eq!(
+            "// Sys.vm\nfunction Sys.init 0\ncall Main.main 0\nreturn\n\
+             // Main.vm\nfunction Main.main 0\npush constant 0\nreturn\n",
+            prog.to_string()
+        );
+        assert_eq!(
+            vec![
+                "dfe: removed unreachable function Main.unused".to_string(),
+                "dfe: removed 1 functions (2 commands)".to_string()
+            ],
+            notes
+        );
+    }
+
+    #[test]
+    fn test_no_entry() {
+        let mut prog = program(&[("Main", "function Main.unused 0\nreturn\n")]);
+        let before = prog.clone();
+
+        Dfe::new("Sys.init").run(&mut prog).unwrap();
+
+        assert_eq!(before, prog);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::command::{ArithmeticOp, Command, Segment};
+use crate::error::VmError;
+use crate::ir::{Located, Program};
+use crate::pass::Pass;
+
+fn fold_op(op: &ArithmeticOp, x: i16, y: i16) -> Option<i16> {
+    let val = match op {
+        ArithmeticOp::Add => x.wrapping_add(y),
+        ArithmeticOp::Subtract => x.wrapping_sub(y),
+        ArithmeticOp::And => x & y,
+        ArithmeticOp::Or => x | y,
+        ArithmeticOp::Equal => -((x == y) as i16),
+        ArithmeticOp::GreaterThan => -((x > y) as i16),
+        ArithmeticOp::LessThan => -((x < y) as i16),
+        _ => return None,
+    };
+
+    // Negative constants cannot be pushed.
+    if val >= 0 {
+        Some(val)
+    } else {
+        None
+    }
+}
+
+fn constant(located: &Located) -> Option<i16> {
+    match located.cmd {
+        Command::Push(Segment::Constant(val)) => Some(val),
+        _ => None,
+    }
+}
+
+/// Replaces `push constant a; push constant b; op` with the result.
+pub struct Fold;
+
+impl Pass for Fold {
+    fn name(&self) -> &'static str {
+        "fold"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let mut folded = 0;
+
+        for module in &mut program.modules {
+            let mut out: Vec<Located> = vec![];
+
+            for located in module.commands.drain(..) {
+                if let Command::Arithmetic(op) = &located.cmd {
+                    let len = out.len();
+                    let operands = match out.get(len.saturating_sub(2)..) {
+                        Some([x, y]) => constant(x).zip(constant(y)),
+                        _ => None,
+                    };
+
+                    if let Some(val) = operands.and_then(|(x, y)| fold_op(op, x, y)) {
+                        out.truncate(len - 1);
+                        out[len - 2].cmd = Command::Push(Segment::Constant(val));
+                        folded += 1;
+                        continue;
+                    }
+                }
+
+                out.push(located);
+            }
+
+            module.commands = out;
+        }
+
+        if folded == 0 {
+            Ok(vec![])
+        } else {
+            Ok(vec![format!("fold: folded {} operations", folded)])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn fold(input: &str) -> String {
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+        Fold.run(&mut program).unwrap();
+
+        program.to_string()
+    }
+
+    #[test]
+    fn test_fold_nested() {
+        let input = "push constant 5\npush constant 2\nadd\npush constant 4\nsub\n";
+        assert_eq!("// Main.vm\npush constant 3\n", fold(input));
+    }
+
+    #[test]
+    fn test_fold_negative_result() {
+        let input = "push constant 1\npush constant 2\nsub\n";
+        assert_eq!(format!("// Main.vm\n{}", input), fold(input));
+    }
+
+    #[test]
+    fn test_fold_across_label() {
+        let input = "push constant 1\nlabel L\npush constant 2\nadd\n";
+        assert_eq!(format!("// Main.vm\n{}", input), fold(input));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::command::{ArithmeticOp, Command, Segment};
+use crate::error::VmError;
+use crate::ir::{Located, Program};
+use crate::pass::Pass;
+use std::collections::HashMap;
+
+/// Replaces calls to small leaf functions with their bodies. Arguments and the
+/// callee's locals move into extra locals of the caller.
+pub struct Inline {
+    /// The largest body, in commands, worth inlining. Zero disables the pass.
+    threshold: usize,
+}
+
+impl Inline {
+    pub fn new(threshold: usize) -> Self {
+        Inline { threshold }
+    }
+}
+
+struct Callee {
+    locals: usize,
+    /// Arguments the body reads, which every call must provide.
+    args: usize,
+    /// `pointer` entries the body sets, which `return` would have restored.
+    pointers: Vec<i16>,
+    /// The body without its final `return`.
+    body: Vec<Located>,
+}
+
+fn stack_effect(cmd: &Command) -> Option<i64> {
+    match cmd {
+        Command::Push(_) => Some(1),
+        Command::Pop(_) => Some(-1),
+        Command::Arithmetic(ArithmeticOp::Negate) | Command::Arithmetic(ArithmeticOp::Not) => {
+            Some(0)
+        }
+        Command::Arithmetic(_) => Some(-1),
+        _ => None,
+    }
+}
+
+/// Accepts straight-line bodies ending in their only `return`, with exactly
+/// the return value left on the stack.
+fn inlinable(locals: usize, body: &[Located]) -> Option<Callee> {
+    let (last, body) = body.split_last()?;
+    if last.cmd != Command::Return {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut args = 0;
+    let mut pointers = vec![];
+
+    for located in body {
+        depth += stack_effect(&located.cmd)?;
+        if depth < 0 {
+            return None;
+        }
+
+        match &located.cmd {
+            Command::Push(Segment::Argument(idx)) | Command::Pop(Segment::Argument(idx)) => {
+                args = args.max(*idx as usize + 1);
+            }
+            Command::Pop(Segment::Pointer(idx)) if !pointers.contains(idx) => {
+                pointers.push(*idx);
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 1 {
+        return None;
+    }
+
+    Some(Callee {
+        locals,
+        args,
+        pointers,
+        body: body.to_vec(),
+    })
+}
+
+fn remap(segment: &Segment, base: i16, arg_cnt: i16) -> Segment {
+    match segment {
+        Segment::Argument(idx) => Segment::Local(base + idx),
+        Segment::Local(idx) => Segment::Local(base + arg_cnt + idx),
+        _ => segment.clone(),
+    }
+}
+
+/// The commands replacing `call` for a callee whose scratch locals start at
+/// `base`.
+fn expand(callee: &Callee, arg_cnt: usize, base: usize, call: &Located) -> Vec<Located> {
+    let at = |cmd| Located {
+        cmd,
+        pos: call.pos.clone(),
+    };
+    let base = base as i16;
+    let arg_cnt = arg_cnt as i16;
+    let saved = base + arg_cnt + callee.locals as i16;
+    let mut out = vec![];
+
+    for idx in (0..arg_cnt).rev() {
+        out.push(at(Command::Pop(Segment::Local(base + idx))));
+    }
+    for idx in 0..callee.locals as i16 {
+        out.push(at(Command::Push(Segment::Constant(0))));
+        out.push(at(Command::Pop(Segment::Local(base + arg_cnt + idx))));
+    }
+    for (slot, ptr) in callee.pointers.iter().enumerate() {
+        out.push(at(Command::Push(Segment::Pointer(*ptr))));
+        out.push(at(Command::Pop(Segment::Local(saved + slot as i16))));
+    }
+
+    for located in &callee.body {
+        let cmd = match &located.cmd {
+            Command::Push(seg) => Command::Push(remap(seg, base, arg_cnt)),
+            Command::Pop(seg) => Command::Pop(remap(seg, base, arg_cnt)),
+            cmd => cmd.clone(),
+        };
+        out.push(Located {
+            cmd,
+            pos: located.pos.clone(),
+        });
+    }
+
+    for (slot, ptr) in callee.pointers.iter().enumerate() {
+        out.push(at(Command::Push(Segment::Local(saved + slot as i16))));
+        out.push(at(Command::Pop(Segment::Pointer(*ptr))));
+    }
+
+    out
+}
+
+impl Inline {
+    fn callees(&self, program: &Program) -> HashMap<String, Callee> {
+        let mut callees = HashMap::new();
+
+        for module in &program.modules {
+            let starts = module
+                .commands
+                .iter()
+                .enumerate()
+                .filter(|(_, located)| matches!(located.cmd, Command::Function(..)))
+                .map(|(idx, _)| idx)
+                .chain(std::iter::once(module.commands.len()))
+                .collect::<Vec<usize>>();
+
+            for bounds in starts.windows(2) {
+                let body = &module.commands[bounds[0] + 1..bounds[1]];
+                if let Command::Function(name, local_cnt) = &module.commands[bounds[0]].cmd {
+                    if body.len() <= self.threshold {
+                        if let Some(callee) = inlinable(*local_cnt, body) {
+                            callees.insert(name.to_string(), callee);
+                        }
+                    }
+                }
+            }
+        }
+
+        callees
+    }
+}
+
+/// Grows the local count of the function declared at `out[idx]`.
+fn add_locals(out: &mut [Located], idx: Option<usize>, extra: usize) {
+    if let Some(idx) = idx {
+        if let Command::Function(_, local_cnt) = &mut out[idx].cmd {
+            *local_cnt += extra;
+        }
+    }
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        if self.threshold == 0 {
+            return Ok(vec![]);
+        }
+
+        let callees = self.callees(program);
+        let mut notes = vec![];
+
+        for module in &mut program.modules {
+            let mut out: Vec<Located> = vec![];
+            // The enclosing function's index in `out`, its own locals and the
+            // most scratch locals any call site in it needs.
+            let mut cur_func: Option<usize> = None;
+            let mut base = 0;
+            let mut scratch = 0;
+
+            for located in module.commands.drain(..) {
+                match &located.cmd {
+                    Command::Function(_, local_cnt) => {
+                        add_locals(&mut out, cur_func, scratch);
+                        cur_func = Some(out.len());
+                        base = *local_cnt;
+                        scratch = 0;
+                    }
+                    Command::Call(func, arg_cnt) if cur_func.is_some() => {
+                        if let Some(callee) = callees.get(func).filter(|x| x.args <= *arg_cnt) {
+                            if let Command::Function(caller, _) = &out[cur_func.unwrap()].cmd {
+                                notes.push(format!(
+                                    "inline: inlined {} into {} at {}",
+                                    func, caller, located.pos
+                                ));
+                            }
+
+                            scratch = scratch.max(*arg_cnt + callee.locals + callee.pointers.len());
+                            out.append(&mut expand(callee, *arg_cnt, base, &located));
+                            continue;
+                        }
+                    }
+                    _ => {}
+                }
+
+                out.push(located);
+            }
+
+            add_locals(&mut out, cur_func, scratch);
+            module.commands = out;
+        }
+
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Vm;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn program(input: &str) -> Program {
+        Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        }
+    }
+
+    fn run(program: &Program) -> Vec<i16> {
+        let cmds = program
+            .commands()
+            .map(|located| located.cmd.clone())
+            .collect::<Vec<Command>>();
+        let mut vm = Vm::new(&cmds);
+        vm.ram_mut()[..5].copy_from_slice(&[256, 256, 0, 3000, 4000]);
+        vm.ram_mut()[3000] = 11;
+        assert!(vm.run(1000));
+
+        // The stack pointer differs, as the caller has more locals.
+        vm.ram()[3..8].to_vec()
+    }
+
+    const INPUT: &str = "function Sys.init 1\n\
+                         push constant 3000\npop pointer 1\n\
+                         push constant 3000\ncall Main.getX 1\n\
+                         push constant 5\npush constant 7\ncall Main.sub 2\nadd\npop temp 0\n\
+                         push pointer 0\npop temp 1\npush pointer 1\npop temp 2\n\
+                         label END\ngoto END\n\
+                         function Main.getX 0\npush argument 0\npop pointer 0\npush this 0\nreturn\n\
+                         function Main.sub 1\npush argument 0\npush argument 1\nsub\npop local 0\n\
+                         push local 0\nreturn\n";
+
+    #[test]
+    fn test_inline() {
+        let mut inlined = program(INPUT);
+        let notes = Inline::new(8).run(&mut inlined).unwrap();
+
+        assert_eq!(
+            vec![
+                "inline: inlined Main.getX into Sys.init at Main.vm:5",
+                "inline: inlined Main.sub into Sys.init at Main.vm:8",
+            ],
+            notes
+        );
+        assert!(inlined
+            .to_string()
+            .starts_with("// Main.vm\nfunction Sys.init 4\n"));
+        assert!(!inlined
+            .commands()
+            .any(|located| matches!(located.cmd, Command::Call(..))));
+        assert_eq!(run(&program(INPUT)), run(&inlined));
+    }
+
+    #[test]
+    fn test_threshold() {
+        let mut inlined = program(INPUT);
+        let notes = Inline::new(4).run(&mut inlined).unwrap();
+
+        assert_eq!(
+            vec!["inline: inlined Main.getX into Sys.init at Main.vm:5"],
+            notes
+        );
+        assert!(Inline::new(0).run(&mut inlined).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_branches_not_inlined() {
+        let input = "function Sys.init 0\npush constant 1\ncall Main.f 1\nreturn\n\
+                     function Main.f 0\npush argument 0\nif-goto A\npush constant 1\nreturn\n\
+                     label A\npush constant 2\nreturn\n";
+        let mut inlined = program(input);
+
+        assert!(Inline::new(100).run(&mut inlined).unwrap().is_empty());
+    }
+}
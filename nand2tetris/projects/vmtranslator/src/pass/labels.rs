@@ -0,0 +1,104 @@
+use crate::command::Command;
+use crate::error::VmError;
+use crate::ir::{Position, Program};
+use crate::pass::Pass;
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Scope<'a> {
+    jumps: Vec<(&'a str, &'a Position)>,
+    labels: HashMap<&'a str, &'a Position>,
+}
+
+/// Checks that every `goto` and `if-goto` names a label in the same function.
+pub struct Labels;
+
+impl Pass for Labels {
+    fn name(&self) -> &'static str {
+        "labels"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let mut errors = vec![];
+
+        for module in &program.modules {
+            // Commands before the first function are scoped to the file.
+            let mut scopes = vec![Scope::default()];
+
+            for located in &module.commands {
+                let scope = scopes.last_mut().unwrap();
+
+                match &located.cmd {
+                    Command::Function(..) => scopes.push(Scope::default()),
+                    Command::Label(lbl) => {
+                        if let Some(prev) = scope.labels.insert(lbl, &located.pos) {
+                            errors.push(VmError::at(
+                                &located.pos,
+                                &format!("label `{}` already defined at {}", lbl, prev),
+                            ));
+                        }
+                    }
+                    Command::Goto(lbl) | Command::IfGoto(lbl) => {
+                        scope.jumps.push((lbl, &located.pos))
+                    }
+                    _ => {}
+                }
+            }
+
+            for scope in &scopes {
+                for (lbl, pos) in &scope.jumps {
+                    if !scope.labels.contains_key(lbl) {
+                        errors.push(VmError::at(pos, &format!("undefined label `{}`", lbl)));
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(vec![])
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn check(input: &str) -> Result<Vec<String>, Vec<VmError>> {
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+
+        Labels.run(&mut program)
+    }
+
+    #[test]
+    fn test_resolved() {
+        let input = "function Main.a 0\nlabel L\ngoto L\nfunction Main.b 0\nlabel L\nif-goto L\n";
+        assert!(check(input).is_ok());
+    }
+
+    #[test]
+    fn test_undefined() {
+        let input = "function Main.a 0\nlabel L\nfunction Main.b 0\ngoto L\n";
+        let errors = check(input).unwrap_err();
+
+        assert_eq!(1, errors.len());
+        assert_eq!("Main.vm:4: undefined label `L`", errors[0].to_string());
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let input = "label L\nlabel L\n";
+        let errors = check(input).unwrap_err();
+
+        assert_eq!(
+            "Main.vm:2: label `L` already defined at Main.vm:1",
+            errors[0].to_string()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::command::Command;
+use crate::error::VmError;
+use crate::ir::{Position, Program};
+use crate::pass::Pass;
+use std::collections::HashMap;
+
+/// Checks that every function is defined exactly once and, if `check_calls`
+/// is set, that every `call` names one of them. Leave it unset for a file
+/// translated on its own, whose calls may be defined elsewhere.
+pub struct Link {
+    check_calls: bool,
+}
+
+impl Link {
+    pub fn new(check_calls: bool) -> Self {
+        Link { check_calls }
+    }
+}
+
+impl Pass for Link {
+    fn name(&self) -> &'static str {
+        "link"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let mut errors = vec![];
+        let mut definitions: HashMap<&str, &Position> = HashMap::new();
+
+        for located in program.commands() {
+            if let Command::Function(func, _) = &located.cmd {
+                if let Some(prev) = definitions.insert(func, &located.pos) {
+                    errors.push(VmError::at(
+                        &located.pos,
+                        &format!("function `{}` already defined at {}", func, prev),
+                    ));
+                }
+            }
+        }
+
+        if self.check_calls {
+            for located in program.commands() {
+                if let Command::Call(func, _) | Command::TailCall(func, _) = &located.cmd {
+                    if !definitions.contains_key(func.as_str()) {
+                        errors.push(VmError::at(
+                            &located.pos,
+                            &format!("call to undefined function `{}`", func),
+                        ));
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(vec![])
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn check(files: &[(&str, &str)], check_calls: bool) -> Result<Vec<String>, Vec<VmError>> {
+        let mut program = Program {
+            modules: files
+                .iter()
+                .map(|(name, input)| Module::parse(BufReader::new(input.as_bytes()), name).unwrap())
+                .collect(),
+        };
+
+        Link::new(check_calls).run(&mut program)
+    }
+
+    #[test]
+    fn test_linked() {
+        let files = [
+            ("Sys", "function Sys.init 0\ncall Main.main 0\n"),
+            ("Main", "function Main.main 0\nreturn\n"),
+        ];
+
+        assert!(check(&files, true).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_definition() {
+        let files = [
+            ("Main", "function Main.main 0\nreturn\n"),
+            ("Other", "\nfunction Main.main 0\nreturn\n"),
+        ];
+
+        let errors = check(&files, false).unwrap_err();
+        assert_eq!(
+            vec!["Other.vm:2: function `Main.main` already defined at Main.vm:1".to_string()],
+            errors
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_undefined_call() {
+        let files = [("Main", "function Main.main 0\ncall Output.printInt 1\n")];
+
+        assert!(check(&files, false).is_ok());
+        let errors = check(&files, true).unwrap_err();
+        assert_eq!(
+            vec!["Main.vm:2: call to undefined function `Output.printInt`".to_string()],
+            errors
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::command::Command;
+use crate::error::VmError;
+use crate::ir::Program;
+use crate::pass::Pass;
+
+/// Rejects our extensions to the VM language, so that translated programs
+/// stay runnable on the course's VM emulator unless asked otherwise.
+pub struct Standard;
+
+impl Pass for Standard {
+    fn name(&self) -> &'static str {
+        "standard"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let errors = program
+            .commands()
+            .filter_map(|located| match &located.cmd {
+                Command::Arithmetic(op) if op.is_extended() => Some(VmError::at(
+                    &located.pos,
+                    &format!("`{}` is an extended command, enable it with --extended", op),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<VmError>>();
+
+        if errors.is_empty() {
+            Ok(vec![])
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn check(input: &str) -> Result<Vec<String>, Vec<VmError>> {
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+
+        Standard.run(&mut program)
+    }
+
+    #[test]
+    fn test_standard() {
+        assert!(check("push constant 2\npush constant 3\nadd\n").is_ok());
+    }
+
+    #[test]
+    fn test_extended() {
+        let errors = check("push constant 2\npush constant 3\nmul\n").unwrap_err();
+
+        assert_eq!(
+            vec!["Main.vm:3: `mul` is an extended command, enable it with --extended"],
+            errors
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+}
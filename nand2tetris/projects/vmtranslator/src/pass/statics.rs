@@ -0,0 +1,246 @@
+use crate::code::routine_variables;
+use crate::command::{Command, Segment};
+use crate::error::VmError;
+use crate::ir::{Position, Program};
+use crate::pass::Pass;
+pub use assembler::assemble::VARIABLE_BASE;
+use std::collections::BTreeMap;
+
+/// Where the bootstrap starts the stack unless told otherwise.
+pub const STACK_BASE: usize = 256;
+
+/// Lays out static slots the way the assembler will, rejecting programs
+/// whose statics and scratch variables run into the stack, and reports the
+/// map when asked to.
+pub struct Statics {
+    stack_base: usize,
+    report: bool,
+}
+
+impl Statics {
+    pub fn new(stack_base: usize, report: bool) -> Self {
+        Statics { stack_base, report }
+    }
+}
+
+/// Formats sorted addresses as runs, like `16-18, 21`.
+fn ranges(addrs: &[usize]) -> String {
+    let mut runs: Vec<(usize, usize)> = vec![];
+
+    for &addr in addrs {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == addr => *end = addr,
+            _ => runs.push((addr, addr)),
+        }
+    }
+
+    runs.iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+impl Pass for Statics {
+    fn name(&self) -> &'static str {
+        "statics"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        // Each `File.n` symbol gets the next address on first use.
+        let mut slots: Vec<(String, i16, Position)> = vec![];
+        let mut variables: Vec<&'static str> = vec![];
+
+        for located in program.commands() {
+            match &located.cmd {
+                Command::Push(Segment::Static(file, offset))
+                | Command::Pop(Segment::Static(file, offset))
+                    if !slots.iter().any(|(f, o, _)| f == file && o == offset) =>
+                {
+                    slots.push((file.to_string(), *offset, located.pos.clone()));
+                }
+                Command::Arithmetic(op) if op.is_extended() => {
+                    for var in routine_variables(op) {
+                        if !variables.contains(var) {
+                            variables.push(var);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut files: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (idx, (file, _, _)) in slots.iter().enumerate() {
+            files
+                .entry(file.as_str())
+                .or_default()
+                .push(VARIABLE_BASE + idx);
+        }
+
+        let mut notes = files
+            .iter()
+            .map(|(file, addrs)| {
+                format!(
+                    "statics: {}.vm uses {} slot(s) at RAM[{}]",
+                    file,
+                    addrs.len(),
+                    ranges(addrs)
+                )
+            })
+            .collect::<Vec<String>>();
+
+        let end = VARIABLE_BASE + slots.len() + variables.len();
+        if !variables.is_empty() {
+            notes.push(format!(
+                "statics: translator scratch variables at RAM[{}]",
+                ranges(&(VARIABLE_BASE + slots.len()..end).collect::<Vec<usize>>())
+            ));
+        }
+
+        if end <= self.stack_base {
+            return Ok(if self.report { notes } else { vec![] });
+        }
+
+        let idx = self.stack_base.saturating_sub(VARIABLE_BASE);
+        let error = match slots.get(idx) {
+            Some((file, offset, pos)) => VmError::at(
+                pos,
+                &format!(
+                    "static {}.{} lands at RAM[{}], inside the stack which starts at {}",
+                    file,
+                    offset,
+                    VARIABLE_BASE + idx,
+                    self.stack_base
+                ),
+            ),
+            None => VmError::new(&format!(
+                "{} static slot(s) and {} scratch variable(s) reach RAM[{}], \
+                 inside the stack which starts at {}",
+                slots.len(),
+                variables.len(),
+                end - 1,
+                self.stack_base
+            )),
+        };
+
+        Err(vec![error])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::Options;
+    use crate::instruct::Instruction;
+    use crate::ir::Module;
+    use crate::translate;
+    use std::collections::BTreeSet;
+    use std::io::BufReader;
+
+    fn program(files: &[(&str, &str)]) -> Program {
+        Program {
+            modules: files
+                .iter()
+                .map(|(name, input)| Module::parse(BufReader::new(input.as_bytes()), name).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_memory_map() {
+        let mut prog = program(&[
+            (
+                "Main",
+                "push static 0\npop static 3\npush static 0\npush constant 2\nmul\n",
+            ),
+            ("Other", "push static 1\n"),
+        ]);
+
+        assert_eq!(
+            vec![
+                "statics: Main.vm uses 2 slot(s) at RAM[16-17]".to_string(),
+                "statics: Other.vm uses 1 slot(s) at RAM[18]".to_string(),
+                "statics: translator scratch variables at RAM[19-20]".to_string(),
+            ],
+            Statics::new(STACK_BASE, true).run(&mut prog).unwrap()
+        );
+        assert!(Statics::new(STACK_BASE, false)
+            .run(&mut prog)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_collision() {
+        let pushes = (0..240)
+            .map(|idx| format!("push static {}\n", idx))
+            .collect::<String>();
+        let mut prog = program(&[("Main", &pushes)]);
+
+        assert!(Statics::new(STACK_BASE, false).run(&mut prog).is_ok());
+        assert_eq!(
+            "Main.vm:240: static Main.239 lands at RAM[255], inside the stack which starts at 255",
+            Statics::new(255, false).run(&mut prog).unwrap_err()[0].to_string()
+        );
+        assert_eq!(
+            "Main.vm:235: static Main.234 lands at RAM[250], inside the stack which starts at 250",
+            Statics::new(250, false).run(&mut prog).unwrap_err()[0].to_string()
+        );
+
+        let mut prog = program(&[("Main", &format!("{}push constant 1\nshr\n", pushes))]);
+        assert_eq!(
+            "240 static slot(s) and 2 scratch variable(s) reach RAM[257], \
+             inside the stack which starts at 256",
+            Statics::new(STACK_BASE, false).run(&mut prog).unwrap_err()[0].to_string()
+        );
+    }
+
+    /// Every variable the translated code declares is accounted for.
+    #[test]
+    fn test_routine_variables() {
+        let input = "push static 0\npush static 1\nmul\npush constant 3\ndiv\n\
+                     push constant 1\nmod\npush constant 2\nshl\npush constant 1\nshr\n";
+        let options = translate::Options {
+            code: Options {
+                bootstrap: None,
+                ..Options::default()
+            },
+            extended: true,
+            ..translate::Options::default()
+        };
+        let instructs = translate::translate(vec![("Main", input.as_bytes())], &options).unwrap();
+
+        let labels = instructs
+            .iter()
+            .filter_map(|x| match x {
+                Instruction::L(lbl) => Some(lbl.as_str()),
+                _ => None,
+            })
+            .collect::<BTreeSet<&str>>();
+        let declared = instructs
+            .iter()
+            .filter_map(|x| match x {
+                Instruction::AVar(var) if var.starts_with("VM_TRANSLATOR_") => Some(var.as_str()),
+                _ => None,
+            })
+            .filter(|var| !labels.contains(var))
+            .collect::<BTreeSet<&str>>();
+
+        let mut prog = program(&[("Main", input)]);
+        let notes = Statics::new(STACK_BASE, true).run(&mut prog).unwrap();
+
+        assert_eq!(
+            format!(
+                "statics: translator scratch variables at RAM[18-{}]",
+                17 + declared.len()
+            ),
+            notes[1]
+        );
+    }
+}
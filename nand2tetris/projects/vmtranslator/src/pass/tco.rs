@@ -0,0 +1,80 @@
+use crate::command::Command;
+use crate::error::VmError;
+use crate::ir::{Located, Program};
+use crate::pass::Pass;
+
+/// Replaces `call f n; return` inside a function with a tail call, so
+/// tail-recursive functions run in constant stack space.
+pub struct Tco;
+
+impl Pass for Tco {
+    fn name(&self) -> &'static str {
+        "tco"
+    }
+
+    fn run(&self, program: &mut Program) -> Result<Vec<String>, Vec<VmError>> {
+        let mut notes = vec![];
+
+        for module in &mut program.modules {
+            let mut out: Vec<Located> = vec![];
+            let mut cur_func = None;
+
+            for located in module.commands.drain(..) {
+                match &located.cmd {
+                    Command::Function(func, _) => cur_func = Some(func.to_string()),
+                    Command::Return => {
+                        if let (Some(func), Some(prev)) = (&cur_func, out.last_mut()) {
+                            if let Command::Call(callee, arg_cnt) = &prev.cmd {
+                                notes.push(format!(
+                                    "tco: {} tail calls {} at {}",
+                                    func, callee, prev.pos
+                                ));
+                                prev.cmd = Command::TailCall(callee.to_string(), *arg_cnt);
+                                continue;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                out.push(located);
+            }
+
+            module.commands = out;
+        }
+
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Module;
+    use std::io::BufReader;
+
+    fn tco(input: &str) -> String {
+        let mut program = Program {
+            modules: vec![Module::parse(BufReader::new(input.as_bytes()), "Main").unwrap()],
+        };
+        Tco.run(&mut program).unwrap();
+
+        program.to_string()
+    }
+
+    #[test]
+    fn test_tail_call() {
+        let input = "function Main.f 0\npush argument 0\ncall Main.g 1\nreturn\n";
+        assert_eq!(
+            "// Main.vm\nfunction Main.f 0\npush argument 0\ntail-call Main.g 1\n",
+            tco(input)
+        );
+    }
+
+    #[test]
+    fn test_not_tail_call() {
+        let input = "call Main.f 0\nreturn\n\
+                     function Main.f 0\ncall Main.g 0\npop temp 0\npush constant 0\nreturn\n";
+        assert_eq!(format!("// Main.vm\n{}", input), tco(input));
+    }
+}
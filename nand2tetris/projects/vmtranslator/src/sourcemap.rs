@@ -0,0 +1,116 @@
+use crate::ir::Position;
+use std::fmt;
+
+/// The ROM addresses `start..end` implementing one VM command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub start: usize,
+    pub end: usize,
+    pub pos: Option<Position>,
+    pub func: Option<String>,
+    pub cmd: String,
+}
+
+/// Maps ROM addresses of the translated program back to VM commands.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    pub entries: Vec<Entry>,
+}
+
+impl SourceMap {
+    pub fn push(&mut self, entry: Entry) {
+        // Commands such as labels occupy no ROM.
+        if entry.start < entry.end {
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn lookup(&self, addr: usize) -> Option<&Entry> {
+        let idx = self.entries.partition_point(|entry| entry.end <= addr);
+        self.entries.get(idx).filter(|entry| entry.start <= addr)
+    }
+}
+
+/// Writes one tab-separated line per command: the first and last ROM address,
+/// file, line, function and command. Unknown fields are written as `-`.
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# start\tend\tfile\tline\tfunction\tcommand")?;
+
+        for entry in &self.entries {
+            let (file, line) = match &entry.pos {
+                Some(pos) => (pos.file.as_str(), pos.line.to_string()),
+                None => ("-", "-".to_string()),
+            };
+
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                entry.start,
+                entry.end - 1,
+                file,
+                line,
+                entry.func.as_deref().unwrap_or("-"),
+                entry.cmd
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_map() -> SourceMap {
+        let mut map = SourceMap::default();
+        map.push(Entry {
+            start: 0,
+            end: 4,
+            pos: None,
+            func: None,
+            cmd: "bootstrap".to_string(),
+        });
+        map.push(Entry {
+            start: 4,
+            end: 4,
+            pos: None,
+            func: None,
+            cmd: "label L".to_string(),
+        });
+        map.push(Entry {
+            start: 4,
+            end: 11,
+            pos: Some(Position {
+                file: "Main.vm".to_string(),
+                line: 3,
+            }),
+            func: Some("Main.main".to_string()),
+            cmd: "push constant 7".to_string(),
+        });
+
+        map
+    }
+
+    #[test]
+    fn test_lookup() {
+        let map = source_map();
+
+        assert_eq!(2, map.entries.len());
+        assert_eq!("bootstrap", map.lookup(3).unwrap().cmd);
+        assert_eq!("push constant 7", map.lookup(4).unwrap().cmd);
+        assert_eq!("push constant 7", map.lookup(10).unwrap().cmd);
+        assert_eq!(None, map.lookup(11));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            "# start\tend\tfile\tline\tfunction\tcommand\n\
+             0\t3\t-\t-\t-\tbootstrap\n\
+             4\t10\tMain.vm\t3\tMain.main\tpush constant 7\n",
+            source_map().to_string()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::code::{self, CodeWriter};
+use crate::error::VmError;
+use crate::instruct::Instruction;
+use crate::ir::Program;
+use crate::pass::{statics, Pipeline};
+use crate::sourcemap::SourceMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, BufWriter, Write};
+
+#[derive(Clone)]
+pub struct Options {
+    pub code: code::Options,
+    /// Drops `code.bootstrap` when the program does not define its entry.
+    pub auto_bootstrap: bool,
+    /// Where execution starts, which dead function elimination keeps.
+    pub entry: String,
+    pub inline_threshold: usize,
+    /// Allows `mul`, `div`, `mod`, `shl` and `shr`.
+    pub extended: bool,
+    pub disabled_passes: Vec<String>,
+    pub dump_after: Vec<String>,
+    /// Rejects calls to functions the program does not define, including the
+    /// bootstrap's, which only holds for a whole program.
+    pub check_calls: bool,
+    /// Adds the static memory map to the notes.
+    pub report_statics: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            code: code::Options::default(),
+            auto_bootstrap: true,
+            entry: "Sys.init".to_string(),
+            inline_threshold: 0,
+            extended: false,
+            disabled_passes: vec![],
+            dump_after: vec![],
+            check_calls: false,
+            report_statics: false,
+        }
+    }
+}
+
+pub struct Translation {
+    pub instructions: Vec<Instruction>,
+    pub source_map: SourceMap,
+    /// What the passes did.
+    pub notes: Vec<String>,
+}
+
+fn io_error(err: std::io::Error) -> Vec<VmError> {
+    vec![VmError::new(&err.to_string())]
+}
+
+/// Runs the passes over `program`, writing requested dumps to `dump`, then
+/// lowers it to assembly.
+pub fn translate_program<W: Write>(
+    program: &mut Program,
+    options: &Options,
+    dump: &mut W,
+) -> Result<Translation, Vec<VmError>> {
+    let stack_base = match &options.code.bootstrap {
+        Some(bootstrap) => usize::try_from(bootstrap.stack_base).map_err(|_| {
+            vec![VmError::new(&format!(
+                "stack base {} is not an address",
+                bootstrap.stack_base
+            ))]
+        })?,
+        None => statics::STACK_BASE,
+    };
+    if let Some(checks) = &options.code.checks {
+        let above_base = usize::try_from(checks.stack_limit).is_ok_and(|limit| limit > stack_base);
+        if !above_base || checks.stack_limit > code::ERROR_ADDR {
+            return Err(vec![VmError::new(&format!(
+                "stack limit {} must be above the stack base {} and at most {}",
+                checks.stack_limit,
+                stack_base,
+                code::ERROR_ADDR
+            ))]);
+        }
+    }
+    let mut pipeline = Pipeline::standard(
+        &options.entry,
+        options.inline_threshold,
+        stack_base,
+        options.check_calls,
+        options.report_statics,
+    );
+    if options.extended {
+        pipeline.disable("standard").map_err(|err| vec![err])?;
+    }
+    for name in &options.disabled_passes {
+        pipeline.disable(name).map_err(|err| vec![err])?;
+    }
+    for name in &options.dump_after {
+        pipeline.dump_after(name).map_err(|err| vec![err])?;
+    }
+
+    let notes = pipeline.run(program, dump)?;
+
+    let mut code_options = options.code.clone();
+    code_options.emit = code::Emit::Asm;
+    if options.auto_bootstrap {
+        code_options.bootstrap = code_options
+            .bootstrap
+            .filter(|bootstrap| program.defines(&bootstrap.entry));
+    }
+    if let Some(bootstrap) = &code_options.bootstrap {
+        if options.check_calls && !program.defines(&bootstrap.entry) {
+            return Err(vec![VmError::new(&format!(
+                "bootstrap calls undefined function `{}`",
+                bootstrap.entry
+            ))]);
+        }
+    }
+
+    let mut writer = CodeWriter::new(BufWriter::new(std::io::sink()), code_options);
+    for module in &program.modules {
+        writer.on_new_file();
+        for located in &module.commands {
+            writer
+                .write_at(&located.cmd, Some(&located.pos))
+                .map_err(io_error)?;
+        }
+    }
+    writer.close().map_err(io_error)?;
+
+    Ok(Translation {
+        instructions: writer.instructions().to_vec(),
+        source_map: writer.source_map().clone(),
+        notes,
+    })
+}
+
+/// Translates `.vm` sources, each named by its file stem, to Hack assembly.
+pub fn translate<N, R, I>(inputs: I, options: &Options) -> Result<Vec<Instruction>, Vec<VmError>>
+where
+    N: AsRef<str>,
+    R: BufRead,
+    I: IntoIterator<Item = (N, R)>,
+{
+    let mut program = Program::parse(inputs)?;
+    let translation = translate_program(&mut program, options, &mut std::io::sink())?;
+
+    Ok(translation.instructions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::Cpu;
+
+    #[test]
+    fn test_translate() {
+        let inputs = [
+            (
+                "Sys",
+                "function Sys.init 0\npush constant 4\ncall Main.double 1\npop temp 0\n\
+                 label END\ngoto END\n"
+                    .as_bytes(),
+            ),
+            (
+                "Main",
+                "function Main.double 0\npush argument 0\npush argument 0\nadd\nreturn\n"
+                    .as_bytes(),
+            ),
+        ];
+
+        let instructs = translate(inputs.iter().copied(), &Options::default()).unwrap();
+        let mut cpu = Cpu::new(&instructs);
+        assert!(cpu.run(10_000));
+        assert_eq!(8, cpu.ram()[5]);
+    }
+
+    #[test]
+    fn test_translate_errors() {
+        let inputs = [
+            ("Main", "push constant 1\npush nowhere 2\n".as_bytes()),
+            ("Other", "frobnicate\n".as_bytes()),
+        ];
+
+        let errors = translate(inputs.iter().copied(), &Options::default()).unwrap_err();
+        assert_eq!(
+            vec![
+                "Main.vm:2: unknown segment `nowhere`",
+                "Other.vm:1: unknown command `frobnicate`",
+            ],
+            errors
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+        );
+    }
+
+    /// A single file may call functions defined in files translated
+    /// separately, as `08/FunctionCalls/FibonacciElement/Sys.vm` does, unless
+    /// it is a whole program.
+    #[test]
+    fn test_translate_single_file() {
+        let inputs = [(
+            "Sys",
+            "function Sys.init 0\npush constant 4\ncall Main.fibonacci 1\n\
+             label WHILE\ngoto WHILE\n"
+                .as_bytes(),
+        )];
+
+        assert!(translate(inputs.iter().copied(), &Options::default()).is_ok());
+
+        let options = Options {
+            check_calls: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            "Sys.vm:3: call to undefined function `Main.fibonacci`",
+            translate(inputs.iter().copied(), &options).unwrap_err()[0].to_string()
+        );
+
+        let inputs = [("Main", "function Main.main 0\nreturn\n".as_bytes())];
+        let options = Options {
+            auto_bootstrap: false,
+            check_calls: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            "bootstrap calls undefined function `Sys.init`",
+            translate(inputs.iter().copied(), &options).unwrap_err()[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        let inputs = [(
+            "Sys",
+            "function Sys.init 0\nlabel END\ngoto END\n".as_bytes(),
+        )];
+        let options = |stack_limit| Options {
+            code: code::Options {
+                checks: Some(code::Checks { stack_limit }),
+                ..code::Options::default()
+            },
+            ..Options::default()
+        };
+
+        assert!(translate(inputs.iter().copied(), &options(code::ERROR_ADDR)).is_ok());
+        for stack_limit in [256, -5, i16::MIN, code::ERROR_ADDR + 1] {
+            assert_eq!(
+                format!(
+                    "stack limit {} must be above the stack base 256 and at most 2047",
+                    stack_limit
+                ),
+                translate(inputs.iter().copied(), &options(stack_limit)).unwrap_err()[0]
+                    .to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_negative_stack_base() {
+        let options = Options {
+            code: code::Options {
+                bootstrap: Some(code::Bootstrap {
+                    stack_base: -1,
+                    ..code::Bootstrap::default()
+                }),
+                ..code::Options::default()
+            },
+            ..Options::default()
+        };
+        let inputs = [(
+            "Sys",
+            "function Sys.init 0\nlabel END\ngoto END\n".as_bytes(),
+        )];
+
+        assert_eq!(
+            "stack base -1 is not an address",
+            translate(inputs.iter().copied(), &options).unwrap_err()[0].to_string()
+        );
+    }
+}